@@ -0,0 +1,82 @@
+// src-tauri/src/atomic_file.rs
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 同一进程内并发写入同一文件时，用序号区分各自的临时文件
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 获取与目标文件同目录的临时文件路径 (保证 rename 不跨文件系统)
+/// 每次调用返回不同的路径
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let seq = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}.{}.tmp", file_name, std::process::id(), seq))
+}
+
+/// 将内容写入临时文件并刷新到磁盘，返回临时文件路径
+pub fn write_temp(path: &Path, contents: &[u8]) -> io::Result<PathBuf> {
+    let tmp = temp_path_for(path);
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(tmp)
+}
+
+/// 先写临时文件再 rename 覆盖目标文件，写入过程中崩溃不会留下半个文件
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = write_temp(path, contents)?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("atomic-file-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn temp_paths_are_unique_per_call() {
+        let path = Path::new("/data/notes.json");
+        let a = temp_path_for(path);
+        let b = temp_path_for(path);
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), path.parent());
+    }
+
+    #[test]
+    fn concurrent_temps_for_the_same_file_do_not_clash() {
+        let dir = test_dir("concurrent");
+        let path = dir.join("a.txt");
+        let first = write_temp(&path, b"first").unwrap();
+        let second = write_temp(&path, b"second").unwrap();
+        assert_eq!(fs::read(&first).unwrap(), b"first");
+        assert_eq!(fs::read(&second).unwrap(), b"second");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_leaves_no_temp_file() {
+        let dir = test_dir("atomic");
+        let path = dir.join("a.txt");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
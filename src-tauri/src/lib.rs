@@ -1,11 +1,28 @@
 // src-tauri/src/lib.rs
 
+mod atomic_file;
 mod backup;
+mod mcp_bridge;
+mod mcp_host;
 mod proxy_server;
 mod remote_service;
+mod script_integrity;
 mod script_manager;
+mod script_protocol;
+mod script_registry;
+mod script_scheduler;
+mod script_sandbox;
 mod search;
+mod search_archive;
+mod search_index;
+mod search_json;
+mod search_replace;
+mod search_semantic;
+mod secrets_audit;
 mod secrets_manager;
+mod secrets_transfer;
+mod secrets_validation;
+mod secrets_vault;
 
 mod error;
 mod machine_id;
@@ -48,6 +65,10 @@ fn cleanup_child_processes(handle: &tauri::AppHandle) {
                 }
             }
         }
+        drop(children_lock);
+
+        // 关闭所有 MCP 服务器
+        mcp_host::stop_all_servers(handle).await;
     });
     println!("Cleanup of child processes finished.");
 }
@@ -72,6 +93,15 @@ pub fn run() {
             // 添加 ScriptProcessState
             children: Mutex::new(HashMap::new()),
         })
+        .manage(script_protocol::PendingSecretRequests::default())
+        .manage(script_scheduler::SchedulerState::new())
+        .manage(mcp_host::McpHostState::new())
+        .manage(mcp_bridge::McpBridgeState::new())
+        .manage(search::SearchState::default())
+        .manage(search_index::SearchIndexState::new())
+        .manage(search_semantic::SemanticIndexState::default())
+        .manage(secrets_manager::SecretsState::default())
+        .manage(secrets_audit::SecretsAuditState::default())
         // 注册端口状态，初始为 0
         .manage(proxy_server::ProxyPort(std::sync::Mutex::new(0)))
         // 注册所有命令
@@ -83,14 +113,67 @@ pub fn run() {
             backup::decompress,
             script_manager::execute_script,
             script_manager::shutdown_script,
+            script_integrity::approve_script,
+            script_integrity::revoke_script_approval,
+            script_integrity::list_script_approvals,
+            script_integrity::get_script_integrity,
+            script_protocol::respond_script_secret,
+            script_registry::list_registered_scripts,
+            script_registry::register_script,
+            script_registry::unregister_script,
+            script_registry::start_all_scripts,
+            script_registry::stop_all_scripts,
+            script_scheduler::list_schedules,
+            script_scheduler::upsert_schedule,
+            script_scheduler::delete_schedule,
+            script_scheduler::run_schedule_now,
+            script_scheduler::get_schedule_history,
+            script_scheduler::clear_schedule_history,
             secrets_manager::get_all_available_keys,
             secrets_manager::is_key_available,
             secrets_manager::write_secret_key,
             secrets_manager::delete_secret_key,
+            secrets_manager::get_secret_restrictions,
+            secrets_manager::set_secret_restrictions,
+            secrets_manager::list_secret_metadata,
+            secrets_manager::update_secret_metadata,
+            secrets_manager::rollback_secret_key,
+            secrets_manager::get_secrets_status,
+            secrets_manager::unlock_secrets,
+            secrets_manager::lock_secrets,
+            secrets_manager::change_master_password,
+            secrets_manager::list_secret_profiles,
+            secrets_manager::create_secret_profile,
+            secrets_manager::rename_secret_profile,
+            secrets_manager::delete_secret_profile,
+            secrets_manager::switch_secret_profile,
+            secrets_transfer::import_secrets,
+            secrets_transfer::export_secrets,
+            secrets_audit::query_secret_audit,
+            secrets_audit::clear_secret_audit,
+            secrets_validation::test_secret,
+            mcp_host::mcp_approve_server,
+            mcp_host::mcp_start_server,
+            mcp_host::mcp_stop_server,
+            mcp_host::mcp_list_servers,
+            mcp_host::mcp_list_tools,
+            mcp_host::mcp_call_tool,
+            mcp_host::mcp_list_resources,
+            mcp_host::mcp_read_resource,
+            mcp_bridge::mcp_bridge_info,
             proxy_server::get_proxy_port,
             remote_service::open_remote_window,
             remote_service::send_to_remote_window,
             search::search_in_files,
+            search::cancel_search,
+            search_index::search_index_query,
+            search_index::search_index_status,
+            search_index::search_index_rebuild,
+            search_semantic::semantic_search,
+            search_replace::preview_replace,
+            search_replace::apply_replace,
+            search_replace::list_replace_undo,
+            search_replace::undo_replace,
             machine_id::get_machine_id
         ])
         .setup(|app| {
@@ -114,6 +197,17 @@ pub fn run() {
                     })
                     .build(app)?;
             }
+            // --- 启动定时任务调度器 ---
+            script_scheduler::init_scheduler(app.handle().clone());
+
+            // --- 加载全文索引并监听数据目录 ---
+            search_index::init_search_index(app.handle().clone());
+
+            // --- 监听密钥文件的外部修改 ---
+            if let Err(e) = secrets_manager::init_secrets_watcher(app.handle()) {
+                eprintln!("[Secrets] Failed to watch secrets file: {}", e);
+            }
+
             // --- 启动代理服务器 ---
             let port = proxy_server::start_proxy_server(app.handle().clone());
             // 将端口号保存到 State 中
@@ -121,6 +215,10 @@ pub fn run() {
             *port_state.0.lock().unwrap() = port;
             println!("Proxy server initialized on port: {}", port);
 
+            // --- 启动 MCP HTTP 桥接服务 ---
+            let bridge_port = mcp_bridge::start_bridge_server(app.handle().clone());
+            *app.state::<mcp_bridge::McpBridgeState>().port.lock().unwrap() = bridge_port;
+
             Ok(())
         })
         .on_window_event(|window, event| match event {
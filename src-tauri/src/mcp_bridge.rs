@@ -0,0 +1,684 @@
+// src-tauri/src/mcp_bridge.rs
+
+use crate::mcp_host::{self, McpHostState, McpServer, McpServerInfo, ServerStatus};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use dashmap::DashMap;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{broadcast, mpsc};
+use tower_http::cors::{Any, CorsLayer};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+// 每个会话最多缓存的待发送消息，客户端不读取时新的通知会被丢弃
+const SESSION_BUFFER: usize = 256;
+// 没有打开的通知流、且超过这段时间没有请求的会话会被清理
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// 桥接服务的端口和访问令牌
+pub struct McpBridgeState {
+    pub port: std::sync::Mutex<u16>,
+    pub token: String,
+}
+
+impl McpBridgeState {
+    pub fn new() -> Self {
+        Self {
+            port: std::sync::Mutex::new(0),
+            token: random_string(32),
+        }
+    }
+}
+
+// 一个 HTTP 客户端会话，绑定到某个 MCP 服务器进程；服务器退出或重启后会话随之失效
+struct BridgeSession {
+    server: String,
+    instance: Weak<McpServer>,
+    // 发往客户端的消息 (通知，以及旧版 SSE 传输下的响应)
+    tx: mpsc::Sender<Value>,
+    rx: std::sync::Mutex<Option<mpsc::Receiver<Value>>>,
+    last_active: std::sync::Mutex<Instant>,
+    // 转发服务器通知的任务，会话结束时中止
+    forwarder: tokio::task::JoinHandle<()>,
+}
+
+impl BridgeSession {
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    fn belongs_to(&self, server: &Arc<McpServer>) -> bool {
+        self.instance.as_ptr() == Arc::as_ptr(server)
+    }
+
+    // 通知流已被取走且仍未关闭，说明客户端正连接着
+    fn is_streaming(&self) -> bool {
+        self.rx.lock().unwrap().is_none() && !self.tx.is_closed()
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        !self.is_streaming()
+            && now.duration_since(*self.last_active.lock().unwrap()) >= SESSION_IDLE_TIMEOUT
+    }
+}
+
+impl Drop for BridgeSession {
+    fn drop(&mut self) {
+        self.forwarder.abort();
+    }
+}
+
+type Sessions = Arc<DashMap<String, Arc<BridgeSession>>>;
+
+#[derive(Clone)]
+struct BridgeState {
+    app: AppHandle,
+    token: Arc<String>,
+    sessions: Sessions,
+}
+
+// SSE 流结束 (客户端断开) 时移除会话
+struct SessionGuard {
+    sessions: Sessions,
+    id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.remove(&self.id);
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionQuery {
+    session_id: Option<String>,
+    token: Option<String>,
+}
+
+// --- 辅助函数 ---
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, message.to_string()).into_response()
+}
+
+// 校验访问令牌：Authorization: Bearer <token> 或 ?token=<token>
+fn check_auth(state: &BridgeState, headers: &HeaderMap, query_token: Option<&str>) -> Result<(), Response> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let provided = bearer.or(query_token).unwrap_or_default();
+
+    // 定长比较，避免通过响应时间猜测令牌
+    let expected = state.token.as_bytes();
+    let matches = provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+
+    if matches {
+        Ok(())
+    } else {
+        Err(error_response(StatusCode::UNAUTHORIZED, "Invalid or missing token"))
+    }
+}
+
+async fn running_server(state: &BridgeState, name: &str) -> Result<Arc<McpServer>, Response> {
+    let host = state.app.state::<McpHostState>();
+    mcp_host::get_server(&host, name)
+        .await
+        .map_err(|e| error_response(StatusCode::NOT_FOUND, &e))
+}
+
+// 创建会话，并开始把服务器通知转发给该会话
+// 服务器进程退出 (包括重启) 时，转发任务会移除会话，客户端需要重新 initialize
+fn create_session(sessions: &Sessions, server: &Arc<McpServer>) -> (String, Arc<BridgeSession>) {
+    let id = random_string(32);
+    let (tx, rx) = mpsc::channel(SESSION_BUFFER);
+
+    let mut notifications = server.notifications.subscribe();
+    let mut status = server.status.subscribe();
+    let notify_tx = tx.clone();
+    let owner = sessions.clone();
+    let session_id = id.clone();
+    let forwarder = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                received = notifications.recv() => match received {
+                    Ok(n) => {
+                        let message = json!({ "jsonrpc": "2.0", "method": n.method, "params": n.params });
+                        match notify_tx.try_send(message) {
+                            // 客户端跟不上时丢弃通知，与广播通道的 lag 行为一致
+                            Err(mpsc::error::TrySendError::Full(_)) => {}
+                            Err(mpsc::error::TrySendError::Closed(_)) => break,
+                            Ok(()) => {}
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = status.wait_for(|s| *s == ServerStatus::Exited) => break,
+            }
+        }
+        owner.remove(&session_id);
+    });
+
+    let session = Arc::new(BridgeSession {
+        server: server.name.clone(),
+        instance: Arc::downgrade(server),
+        tx,
+        rx: std::sync::Mutex::new(Some(rx)),
+        last_active: std::sync::Mutex::new(Instant::now()),
+        forwarder,
+    });
+    sessions.insert(id.clone(), session.clone());
+    (id, session)
+}
+
+// 使用 stdio 服务器握手时得到的信息回应客户端的 initialize
+fn initialize_result(info: Option<McpServerInfo>) -> Value {
+    let info = match info {
+        Some(info) => info,
+        None => return json!({ "protocolVersion": mcp_host::PROTOCOL_VERSION, "capabilities": {} }),
+    };
+    let mut result = json!({
+        "protocolVersion": info.protocol_version.unwrap_or_else(|| mcp_host::PROTOCOL_VERSION.into()),
+        "capabilities": info.capabilities,
+        "serverInfo": info.server_info,
+    });
+    if let Some(instructions) = info.instructions {
+        result["instructions"] = json!(instructions);
+    }
+    result
+}
+
+fn is_initialize(message: &Value) -> bool {
+    message.get("method").and_then(|m| m.as_str()) == Some("initialize")
+}
+
+/// 处理客户端发来的一条 JSON-RPC 消息，请求会返回对应的响应
+/// 转发给 stdio 服务器时使用服务器侧的新 id，响应中再换回客户端原来的 id，
+/// 这样不同会话的 id 不会冲突
+async fn handle_client_message(server: &McpServer, message: Value) -> Option<Value> {
+    let method = message.get("method").and_then(|m| m.as_str()).map(String::from);
+    let id = message.get("id").cloned();
+    let params = message.get("params").cloned();
+
+    match (method, id) {
+        (Some(method), Some(id)) => {
+            let mut response = match method.as_str() {
+                "initialize" => json!({ "result": initialize_result(server.info.lock().await.clone()) }),
+                "ping" => json!({ "result": {} }),
+                _ => match server.request_raw(&method, params).await {
+                    Ok(response) => response,
+                    Err(e) => json!({ "error": { "code": -32603, "message": e } }),
+                },
+            };
+            response["jsonrpc"] = json!("2.0");
+            response["id"] = id;
+            Some(response)
+        }
+        (Some(method), None) => {
+            // initialized 已由后端完成；cancelled 引用的是客户端 id，无法安全转发
+            if method != "notifications/initialized" && method != "notifications/cancelled" {
+                if let Err(e) = server.notify(&method, params).await {
+                    eprintln!("[MCP Bridge] Failed to forward notification: {}", e);
+                }
+            }
+            None
+        }
+        // 客户端对服务器请求的响应：后端不会转发服务器请求，直接忽略
+        _ => None,
+    }
+}
+
+// 处理单条或批量消息，返回需要回给客户端的响应
+async fn handle_body(server: &McpServer, body: Value) -> Option<Value> {
+    match body {
+        Value::Array(messages) => {
+            let mut responses = Vec::new();
+            for message in messages {
+                if let Some(response) = handle_client_message(server, message).await {
+                    responses.push(response);
+                }
+            }
+            if responses.is_empty() {
+                None
+            } else {
+                Some(Value::Array(responses))
+            }
+        }
+        message => handle_client_message(server, message).await,
+    }
+}
+
+fn parse_body(body: &Bytes) -> Result<Value, Response> {
+    serde_json::from_slice(body).map_err(|e| {
+        let error = json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": format!("Parse error: {}", e) }
+        });
+        (StatusCode::BAD_REQUEST, Json(error)).into_response()
+    })
+}
+
+fn session_from_headers(
+    state: &BridgeState,
+    headers: &HeaderMap,
+    server: &str,
+) -> Result<(String, Arc<BridgeSession>), Response> {
+    let id = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header"))?;
+    lookup_session(state, id, server)
+}
+
+fn lookup_session(
+    state: &BridgeState,
+    id: &str,
+    server: &str,
+) -> Result<(String, Arc<BridgeSession>), Response> {
+    match state.sessions.get(id) {
+        Some(session) if session.server == server => {
+            session.touch();
+            Ok((id.to_string(), session.clone()))
+        }
+        _ => Err(error_response(StatusCode::NOT_FOUND, "Unknown session")),
+    }
+}
+
+// 把会话的消息通道转换为 SSE 流
+fn sse_stream(
+    rx: mpsc::Receiver<Value>,
+    first: Option<Event>,
+    guard: Option<SessionGuard>,
+) -> Sse<impl futures_util::Stream<Item = Result<Event, Infallible>>> {
+    let messages = futures_util::stream::unfold((rx, guard), |(mut rx, guard)| async move {
+        let message = rx.recv().await?;
+        let event = Event::default()
+            .event("message")
+            .data(serde_json::to_string(&message).unwrap_or_default());
+        Some((Ok(event), (rx, guard)))
+    });
+    let first = futures_util::stream::iter(first.into_iter().map(Ok));
+    Sse::new(futures_util::StreamExt::chain(first, messages)).keep_alive(KeepAlive::default())
+}
+
+// --- Streamable HTTP 传输: /mcp/:server ---
+
+async fn streamable_post(
+    State(state): State<BridgeState>,
+    Path(server_name): Path<String>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(res) = check_auth(&state, &headers, query.token.as_deref()) {
+        return res;
+    }
+    let server = match running_server(&state, &server_name).await {
+        Ok(s) => s,
+        Err(res) => return res,
+    };
+    let body = match parse_body(&body) {
+        Ok(b) => b,
+        Err(res) => return res,
+    };
+
+    // initialize 请求创建新会话
+    if is_initialize(&body) {
+        let (session_id, _) = create_session(&state.sessions, &server);
+        let response = handle_body(&server, body).await;
+        let mut res = Json(response.unwrap_or(Value::Null)).into_response();
+        if let Ok(value) = HeaderValue::from_str(&session_id) {
+            res.headers_mut().insert(SESSION_HEADER, value);
+        }
+        return res;
+    }
+
+    match session_from_headers(&state, &headers, &server_name) {
+        Ok((_, session)) if session.belongs_to(&server) => {}
+        Ok((id, _)) => {
+            // 会话属于已经退出的服务器进程
+            state.sessions.remove(&id);
+            return error_response(StatusCode::NOT_FOUND, "Unknown session");
+        }
+        Err(res) => return res,
+    }
+
+    match handle_body(&server, body).await {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+// 服务器到客户端的通知流 (每个会话只能打开一个)
+async fn streamable_get(
+    State(state): State<BridgeState>,
+    Path(server_name): Path<String>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(res) = check_auth(&state, &headers, query.token.as_deref()) {
+        return res;
+    }
+    let (_, session) = match session_from_headers(&state, &headers, &server_name) {
+        Ok(s) => s,
+        Err(res) => return res,
+    };
+    let rx = session.rx.lock().unwrap().take();
+    match rx {
+        Some(rx) => sse_stream(rx, None, None).into_response(),
+        None => error_response(StatusCode::CONFLICT, "Stream already open for this session"),
+    }
+}
+
+async fn streamable_delete(
+    State(state): State<BridgeState>,
+    Path(server_name): Path<String>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(res) = check_auth(&state, &headers, query.token.as_deref()) {
+        return res;
+    }
+    match session_from_headers(&state, &headers, &server_name) {
+        Ok((id, _)) => {
+            state.sessions.remove(&id);
+            StatusCode::OK.into_response()
+        }
+        Err(res) => res,
+    }
+}
+
+// --- 旧版 HTTP+SSE 传输: GET /sse/:server + POST /messages/:server?sessionId= ---
+
+async fn sse_connect(
+    State(state): State<BridgeState>,
+    Path(server_name): Path<String>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(res) = check_auth(&state, &headers, query.token.as_deref()) {
+        return res;
+    }
+    let server = match running_server(&state, &server_name).await {
+        Ok(s) => s,
+        Err(res) => return res,
+    };
+
+    let (session_id, session) = create_session(&state.sessions, &server);
+    let rx = session.rx.lock().unwrap().take().expect("new session has a receiver");
+    let guard = SessionGuard {
+        sessions: state.sessions.clone(),
+        id: session_id.clone(),
+    };
+
+    // 第一个事件告诉客户端往哪里 POST 消息
+    let endpoint = format!(
+        "/messages/{}?sessionId={}&token={}",
+        server_name, session_id, state.token
+    );
+    let first = Event::default().event("endpoint").data(endpoint);
+    sse_stream(rx, Some(first), Some(guard)).into_response()
+}
+
+async fn sse_message(
+    State(state): State<BridgeState>,
+    Path(server_name): Path<String>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(res) = check_auth(&state, &headers, query.token.as_deref()) {
+        return res;
+    }
+    let session_id = match query.session_id.as_deref() {
+        Some(id) => id,
+        None => return error_response(StatusCode::BAD_REQUEST, "Missing sessionId"),
+    };
+    let (_, session) = match lookup_session(&state, session_id, &server_name) {
+        Ok(s) => s,
+        Err(res) => return res,
+    };
+    let server = match running_server(&state, &server_name).await {
+        Ok(s) if session.belongs_to(&s) => s,
+        Ok(_) => {
+            state.sessions.remove(session_id);
+            return error_response(StatusCode::NOT_FOUND, "Unknown session");
+        }
+        Err(res) => return res,
+    };
+    let body = match parse_body(&body) {
+        Ok(b) => b,
+        Err(res) => return res,
+    };
+
+    // 响应通过 SSE 流异步返回，这里立即回 202；流关闭 (会话结束) 时发送失败并丢弃
+    tokio::spawn(async move {
+        if let Some(response) = handle_body(&server, body).await {
+            let _ = session.tx.send(response).await;
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// 启动桥接服务器，返回监听的端口
+pub fn start_bridge_server(app: AppHandle) -> u16 {
+    let port = portpicker::pick_unused_port().unwrap_or(14510);
+    let token = app.state::<McpBridgeState>().token.clone();
+
+    let state = BridgeState {
+        app: app.clone(),
+        token: Arc::new(token),
+        sessions: Arc::new(DashMap::new()),
+    };
+
+    // 定期清理长时间不活动的会话 (客户端既没有 GET 通知流，也没有 DELETE)
+    let sessions = state.sessions.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            sessions.retain(|_, session| !session.is_expired(now));
+        }
+    });
+
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([header::HeaderName::from_static(SESSION_HEADER)]);
+
+    let router = Router::new()
+        .route(
+            "/mcp/:server",
+            post(streamable_post)
+                .get(streamable_get)
+                .delete(streamable_delete),
+        )
+        .route("/sse/:server", get(sse_connect))
+        .route("/messages/:server", post(sse_message))
+        .layer(cors)
+        .with_state(state);
+
+    tauri::async_runtime::spawn(async move {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+        println!("MCP bridge running on http://{}", addr);
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    port
+}
+
+// 前端连接桥接服务所需的信息
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpBridgeEndpoint {
+    pub streamable_http: String,
+    pub sse: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpBridgeInfo {
+    pub port: u16,
+    pub token: String,
+    // 正在运行的服务器 -> 访问地址
+    pub endpoints: HashMap<String, McpBridgeEndpoint>,
+}
+
+// --- Tauri Commands ---
+
+#[tauri::command]
+pub async fn mcp_bridge_info(
+    bridge: tauri::State<'_, McpBridgeState>,
+    host: tauri::State<'_, McpHostState>,
+) -> Result<McpBridgeInfo, String> {
+    let port = *bridge.port.lock().unwrap();
+    let base = format!("http://127.0.0.1:{}", port);
+    let endpoints = host
+        .servers
+        .lock()
+        .await
+        .keys()
+        .map(|name| {
+            (
+                name.clone(),
+                McpBridgeEndpoint {
+                    streamable_http: format!("{}/mcp/{}", base, name),
+                    sse: format!("{}/sse/{}", base, name),
+                },
+            )
+        })
+        .collect();
+
+    Ok(McpBridgeInfo {
+        port,
+        token: bridge.token.clone(),
+        endpoints,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp_host::{McpNotification, ServerProcess};
+
+    struct NullProcess;
+
+    impl ServerProcess for NullProcess {
+        fn write(&mut self, _data: &[u8]) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn kill(self: Box<Self>) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn notify(server: &McpServer, method: &str) {
+        let _ = server.notifications.send(McpNotification {
+            server: server.name.clone(),
+            method: method.to_string(),
+            params: Value::Null,
+        });
+    }
+
+    #[tokio::test]
+    async fn slow_clients_lose_notifications_instead_of_buffering_them() {
+        let sessions: Sessions = Arc::new(DashMap::new());
+        let server = McpServer::new("dummy", Box::new(NullProcess));
+        let (_, session) = create_session(&sessions, &server);
+        let mut rx = session.rx.lock().unwrap().take().unwrap();
+
+        for _ in 0..SESSION_BUFFER * 4 {
+            notify(&server, "notifications/progress");
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut buffered = 0;
+        while rx.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert!(buffered > 0 && buffered <= SESSION_BUFFER, "{}", buffered);
+
+        // 丢弃之后转发仍在继续
+        notify(&server, "notifications/last");
+        let message = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message["method"], "notifications/last");
+    }
+
+    #[tokio::test]
+    async fn sessions_end_when_the_server_exits() {
+        let sessions: Sessions = Arc::new(DashMap::new());
+        let server = McpServer::new("dummy", Box::new(NullProcess));
+        let (id, session) = create_session(&sessions, &server);
+        let mut rx = session.rx.lock().unwrap().take().unwrap();
+
+        let restarted = McpServer::new("dummy", Box::new(NullProcess));
+        assert!(session.belongs_to(&server));
+        assert!(!session.belongs_to(&restarted));
+
+        server.status.send_replace(ServerStatus::Exited);
+        drop(session);
+        // 会话被移除，通知流随之结束
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while rx.recv().await.is_some() {}
+        })
+        .await
+        .unwrap();
+        assert!(!sessions.contains_key(&id));
+    }
+
+    #[tokio::test]
+    async fn idle_sessions_expire_unless_a_stream_is_open() {
+        let sessions: Sessions = Arc::new(DashMap::new());
+        let server = McpServer::new("dummy", Box::new(NullProcess));
+        let (_, session) = create_session(&sessions, &server);
+        let later = Instant::now() + SESSION_IDLE_TIMEOUT;
+
+        assert!(!session.is_expired(Instant::now()));
+        assert!(session.is_expired(later));
+
+        let rx = session.rx.lock().unwrap().take();
+        assert!(!session.is_expired(later));
+        drop(rx);
+        assert!(session.is_expired(later));
+    }
+}
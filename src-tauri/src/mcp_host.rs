@@ -0,0 +1,828 @@
+// src-tauri/src/mcp_host.rs
+
+use crate::script_sandbox::{self, ResourceLimits};
+use crate::script_integrity::{self, ScriptApproval};
+use crate::{script_manager, script_registry};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+
+// 客户端声明的 MCP 协议版本
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+// 单个请求的默认超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+// manifest.json 中 mcpServers 的单项配置 (与前端 McpServerConfig 对应)
+// 启动方式二选一：
+// - script:  已注册脚本的 name，与普通脚本一样经过完整性校验，并使用注册信息中的资源限制和受限模式
+//            (此时忽略 args / limits / restricted)
+// - command: 可执行文件 (例如 node 或绝对路径)，其入口文件需经用户批准 (mcp_approve_server)，
+//            并应用此处配置的 limits / restricted
+#[derive(Clone, Debug, Deserialize)]
+pub struct McpServerConfig {
+    #[serde(default)]
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub limits: Option<ResourceLimits>,
+    #[serde(default)]
+    pub restricted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    mcp_servers: HashMap<String, McpServerConfig>,
+}
+
+// 返回给前端的服务器信息 (initialize 的结果)
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerInfo {
+    pub name: String,
+    pub protocol_version: Option<String>,
+    pub server_info: Value,
+    pub capabilities: Value,
+    pub instructions: Option<String>,
+}
+
+// 服务器主动发来的通知
+#[derive(Clone, Debug, Serialize)]
+pub struct McpNotification {
+    pub server: String,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Clone, Serialize)]
+struct McpLog {
+    server: String,
+    line: String,
+}
+
+#[derive(Clone, Serialize)]
+struct McpExited {
+    server: String,
+    code: Option<i32>,
+    signal: Option<i32>,
+}
+
+// 服务器进程：向 stdin 写入消息、结束进程 (测试中替换为普通的子进程)
+pub(crate) trait ServerProcess: Send {
+    fn write(&mut self, data: &[u8]) -> Result<(), String>;
+    fn kill(self: Box<Self>) -> Result<(), String>;
+}
+
+impl ServerProcess for CommandChild {
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        CommandChild::write(self, data).map_err(|e| e.to_string())
+    }
+
+    fn kill(self: Box<Self>) -> Result<(), String> {
+        CommandChild::kill(*self).map_err(|e| e.to_string())
+    }
+}
+
+// 服务器的生命周期；initialize 握手完成前为 Starting，此时其他调用会等待握手结束
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerStatus {
+    Starting,
+    Ready,
+    Failed(String),
+    Exited,
+}
+
+// 一个正在运行的 stdio MCP 服务器
+pub struct McpServer {
+    pub name: String,
+    child: Mutex<Option<Box<dyn ServerProcess>>>,
+    next_id: AtomicU64,
+    // 等待响应的请求: id -> 完整的 JSON-RPC 响应
+    pending: Mutex<HashMap<u64, oneshot::Sender<Value>>>,
+    // 通知广播，供事件转发和 HTTP 桥接订阅
+    pub notifications: broadcast::Sender<McpNotification>,
+    pub info: Mutex<Option<McpServerInfo>>,
+    pub status: watch::Sender<ServerStatus>,
+}
+
+impl McpServer {
+    pub(crate) fn new(name: &str, process: Box<dyn ServerProcess>) -> Arc<Self> {
+        let (notifications, _) = broadcast::channel(256);
+        Arc::new(Self {
+            name: name.to_string(),
+            child: Mutex::new(Some(process)),
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            notifications,
+            info: Mutex::new(None),
+            status: watch::Sender::new(ServerStatus::Starting),
+        })
+    }
+
+    /// 等待 initialize 握手结束；握手失败或进程已退出时返回错误
+    pub async fn wait_ready(&self) -> Result<(), String> {
+        let mut rx = self.status.subscribe();
+        let status = rx
+            .wait_for(|s| *s != ServerStatus::Starting)
+            .await
+            .map_err(|_| format!("MCP server '{}' is not running", self.name))?;
+        match &*status {
+            ServerStatus::Ready => Ok(()),
+            ServerStatus::Failed(e) => Err(e.clone()),
+            _ => Err(format!("MCP server '{}' is not running", self.name)),
+        }
+    }
+
+    // 向服务器的 stdin 写入一条消息 (每条消息一行)
+    async fn send(&self, message: &Value) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let mut child = self.child.lock().await;
+        let child = child
+            .as_mut()
+            .ok_or_else(|| format!("MCP server '{}' is not running", self.name))?;
+        child.write(line.as_bytes())
+    }
+
+    /// 发送请求并等待完整的 JSON-RPC 响应 (包含 result 或 error)
+    pub async fn request_raw(&self, method: &str, params: Option<Value>) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+
+        let mut message = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        if let Err(e) = self.send(&message).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(format!("MCP server '{}' exited", self.name)),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(format!("MCP request '{}' timed out", method))
+            }
+        }
+    }
+
+    /// 发送请求，返回 result；JSON-RPC 错误转换为 Err
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<Value, String> {
+        let response = self.request_raw(method, params).await?;
+        if let Some(error) = response.get("error") {
+            return Err(format!(
+                "MCP error {}: {}",
+                error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+                error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error")
+            ));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// 发送通知 (没有 id，不等待响应)
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> Result<(), String> {
+        let mut message = json!({ "jsonrpc": "2.0", "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        self.send(&message).await
+    }
+
+    /// 回应服务器发来的请求
+    pub async fn respond(&self, id: Value, result: Result<Value, (i64, String)>) -> Result<(), String> {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message }
+            }),
+        };
+        self.send(&message).await
+    }
+
+    async fn kill(&self) -> Result<(), String> {
+        if let Some(child) = self.child.lock().await.take() {
+            child.kill()?;
+        }
+        Ok(())
+    }
+
+    // initialize 握手，结束后状态变为 Ready 或 Failed
+    async fn initialize(&self) -> Result<(), String> {
+        let result = self.handshake().await;
+        self.status.send_if_modified(|status| {
+            if *status != ServerStatus::Starting {
+                return false;
+            }
+            *status = match &result {
+                Ok(()) => ServerStatus::Ready,
+                Err(e) => ServerStatus::Failed(e.clone()),
+            };
+            true
+        });
+        result
+    }
+
+    async fn handshake(&self) -> Result<(), String> {
+        let init = self
+            .request(
+                "initialize",
+                Some(json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "pulsar", "version": env!("CARGO_PKG_VERSION") }
+                })),
+            )
+            .await
+            .map_err(|e| format!("MCP initialize failed for '{}': {}", self.name, e))?;
+
+        self.notify("notifications/initialized", None).await?;
+
+        *self.info.lock().await = Some(McpServerInfo {
+            name: self.name.clone(),
+            protocol_version: init
+                .get("protocolVersion")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            server_info: init.get("serverInfo").cloned().unwrap_or(Value::Null),
+            capabilities: init.get("capabilities").cloned().unwrap_or(Value::Null),
+            instructions: init
+                .get("instructions")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        });
+        Ok(())
+    }
+
+    // 进程结束：丢弃所有等待中的请求，并标记为已退出
+    async fn on_exit(&self) {
+        self.pending.lock().await.clear();
+        self.child.lock().await.take();
+        self.status.send_replace(ServerStatus::Exited);
+    }
+
+    // 处理服务器 stdout 上的一行消息，通知会广播给订阅方并返回，由调用方转发给前端
+    async fn handle_message(&self, message: Value) -> Option<McpNotification> {
+        let method = message.get("method").and_then(|m| m.as_str()).map(String::from);
+        let id = message.get("id").cloned();
+
+        match (method, id) {
+            // 响应
+            (None, Some(id)) => {
+                if let Some(id) = id.as_u64() {
+                    if let Some(tx) = self.pending.lock().await.remove(&id) {
+                        let _ = tx.send(message);
+                    }
+                }
+                None
+            }
+            // 服务器发来的请求：目前只支持 ping，其余返回 method not found
+            (Some(method), Some(id)) => {
+                let result = if method == "ping" {
+                    Ok(json!({}))
+                } else {
+                    Err((-32601, format!("Method not supported by client: {}", method)))
+                };
+                if let Err(e) = self.respond(id, result).await {
+                    eprintln!("[MCP] Failed to respond to '{}': {}", self.name, e);
+                }
+                None
+            }
+            // 通知
+            (Some(method), None) => {
+                let notification = McpNotification {
+                    server: self.name.clone(),
+                    method,
+                    params: message.get("params").cloned().unwrap_or(Value::Null),
+                };
+                let _ = self.notifications.send(notification.clone());
+                Some(notification)
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+// 管理所有运行中的 MCP 服务器
+pub struct McpHostState {
+    pub servers: Mutex<HashMap<String, Arc<McpServer>>>,
+}
+
+impl McpHostState {
+    pub fn new() -> Self {
+        Self {
+            servers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// --- 辅助函数 ---
+
+// 从 <app_data_dir>/manifest.json 读取 mcpServers 配置
+pub fn read_server_configs(app: &AppHandle) -> Result<HashMap<String, McpServerConfig>, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let manifest_path = app_data_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = std::fs::read_to_string(manifest_path).map_err(|e| e.to_string())?;
+    let manifest: Manifest =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid manifest.json: {}", e))?;
+    Ok(manifest.mcp_servers)
+}
+
+// 获取正在运行的服务器；服务器仍在启动时等待握手完成
+pub async fn get_server(state: &McpHostState, name: &str) -> Result<Arc<McpServer>, String> {
+    let server = state
+        .servers
+        .lock()
+        .await
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("MCP server '{}' is not running", name))?;
+    server.wait_ready().await?;
+    Ok(server)
+}
+
+// 查找可执行文件并规范化路径，完整性校验以规范化后的路径为准
+fn resolve_program(command: &str) -> Result<PathBuf, String> {
+    let path = Path::new(command);
+    let found = if path.is_absolute() || path.components().count() > 1 {
+        Some(path.to_path_buf())
+    } else if cfg!(windows) {
+        ["", ".exe", ".cmd", ".bat"]
+            .iter()
+            .find_map(|ext| script_sandbox::find_in_path(&format!("{}{}", command, ext)))
+    } else {
+        script_sandbox::find_in_path(command)
+    };
+    found
+        .and_then(|p| p.canonicalize().ok())
+        .ok_or_else(|| format!("MCP server command not found: {}", command))
+}
+
+// 只负责加载入口文件的解释器和包运行器，批准它们本身无法固定服务器的代码
+const RUNNERS: &[&str] = &[
+    "node", "npx", "bun", "bunx", "deno", "python", "python3", "uv", "uvx", "npm", "pnpm", "yarn",
+];
+
+// 需要批准并校验完整性的文件：参数中第一个本地文件 (例如 node /srv/server.js 中的 server.js)，
+// 没有时为可执行文件本身；解释器或包运行器 (例如 npx -y <包名>) 没有本地入口文件时拒绝启动
+fn resolve_entry(name: &str, program: &Path, args: &[String]) -> Result<PathBuf, String> {
+    let entry = args
+        .iter()
+        .filter(|arg| !arg.starts_with('-'))
+        .filter_map(|arg| Path::new(arg).canonicalize().ok())
+        .find(|path| path.is_file());
+    if let Some(entry) = entry {
+        return Ok(entry);
+    }
+
+    let stem = program
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if RUNNERS.contains(&stem.as_str()) {
+        return Err(format!(
+            "MCP server '{}' runs {} without a local entry file, so its code cannot be approved; \
+             point args at the server file or launch it through a registered script",
+            name, stem
+        ));
+    }
+    Ok(program.to_path_buf())
+}
+
+// 通过脚本层构建启动命令 (完整性校验、资源限制、受限模式)
+fn build_launch(
+    app: &AppHandle,
+    name: &str,
+    config: &McpServerConfig,
+) -> Result<(String, Vec<String>), String> {
+    if let Some(script) = &config.script {
+        let entry = script_registry::read_registry(app)?
+            .into_iter()
+            .find(|e| &e.name == script)
+            .ok_or_else(|| {
+                format!("MCP server '{}' uses unregistered script '{}'", name, script)
+            })?;
+        let prepared = script_manager::prepare_script(app, &entry.path)?;
+        return Ok((prepared.cmd, prepared.args));
+    }
+
+    if config.command.trim().is_empty() {
+        return Err(format!("MCP server '{}' has neither a command nor a script", name));
+    }
+    let program = resolve_program(&config.command)?;
+    script_integrity::verify_script(app, &resolve_entry(name, &program, &config.args)?)?;
+    script_sandbox::build_program_command(
+        &program.to_string_lossy(),
+        &config.args,
+        config.limits.as_ref(),
+        config.restricted,
+        &script_manager::scratch_dir(app, &format!("mcp-{}", name))?,
+    )
+}
+
+// 从状态中移除服务器 (只移除同一个实例，避免误删重新启动后的新实例)
+async fn remove_server(app: &AppHandle, server: &Arc<McpServer>) {
+    let state = app.state::<McpHostState>();
+    let mut servers = state.servers.lock().await;
+    if servers
+        .get(&server.name)
+        .map(|s| Arc::ptr_eq(s, server))
+        .unwrap_or(false)
+    {
+        servers.remove(&server.name);
+    }
+}
+
+/// 启动 manifest.json 中配置的服务器并完成 initialize 握手
+/// 服务器已在运行时直接返回；正在启动时等待其握手完成
+pub async fn start_server(app: &AppHandle, name: &str) -> Result<Arc<McpServer>, String> {
+    let state = app.state::<McpHostState>();
+    let mut servers = state.servers.lock().await;
+    if let Some(server) = servers.get(name).cloned() {
+        drop(servers);
+        server.wait_ready().await?;
+        return Ok(server);
+    }
+
+    let config = read_server_configs(app)?
+        .remove(name)
+        .ok_or_else(|| format!("MCP server '{}' not found in manifest.json", name))?;
+    let (cmd, args) = build_launch(app, name, &config)?;
+
+    let (mut rx, child) = app
+        .shell()
+        .command(cmd)
+        .args(args)
+        .envs(config.env.clone())
+        .spawn()
+        .map_err(|e| format!("Failed to start MCP server '{}': {}", name, e))?;
+
+    // 握手完成前服务器处于 Starting 状态，其他调用会等待而不是失败
+    let server = McpServer::new(name, Box::new(child));
+    servers.insert(name.to_string(), server.clone());
+    drop(servers);
+
+    // 异步读取服务器输出
+    let app_clone = app.clone();
+    let reader = server.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut exit_code = None;
+        let mut exit_signal = None;
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<Value>(line) {
+                        Ok(message) => {
+                            if let Some(notification) = reader.handle_message(message).await {
+                                let _ = app_clone.emit("mcp-notification", notification);
+                            }
+                        }
+                        // 不是 JSON 的输出当作日志处理
+                        Err(_) => {
+                            let _ = app_clone.emit(
+                                "mcp-log",
+                                McpLog {
+                                    server: reader.name.clone(),
+                                    line: line.to_string(),
+                                },
+                            );
+                        }
+                    }
+                }
+                CommandEvent::Stderr(line) => {
+                    let _ = app_clone.emit(
+                        "mcp-log",
+                        McpLog {
+                            server: reader.name.clone(),
+                            line: String::from_utf8_lossy(&line).trim_end().to_string(),
+                        },
+                    );
+                }
+                CommandEvent::Error(e) => {
+                    let _ = app_clone.emit(
+                        "mcp-log",
+                        McpLog {
+                            server: reader.name.clone(),
+                            line: e,
+                        },
+                    );
+                }
+                CommandEvent::Terminated(payload) => {
+                    exit_code = payload.code;
+                    exit_signal = payload.signal;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // 进程结束：丢弃所有等待中的请求，并从状态中移除
+        reader.on_exit().await;
+        remove_server(&app_clone, &reader).await;
+        let _ = app_clone.emit(
+            "mcp-server-exited",
+            McpExited {
+                server: reader.name.clone(),
+                code: exit_code,
+                signal: exit_signal,
+            },
+        );
+        println!("[MCP] Server '{}' exited", reader.name);
+    });
+
+    if let Err(e) = server.initialize().await {
+        remove_server(app, &server).await;
+        let _ = server.kill().await;
+        return Err(e);
+    }
+    println!("[MCP] Server '{}' initialized", name);
+
+    Ok(server)
+}
+
+pub async fn stop_server(app: &AppHandle, name: &str) -> Result<(), String> {
+    let state = app.state::<McpHostState>();
+    let server = state.servers.lock().await.remove(name);
+    match server {
+        Some(server) => server.kill().await,
+        None => Err(format!("MCP server '{}' is not running", name)),
+    }
+}
+
+/// 结束所有 MCP 服务器，在应用退出时调用
+pub async fn stop_all_servers(app: &AppHandle) {
+    let state = app.state::<McpHostState>();
+    let servers: Vec<Arc<McpServer>> = state.servers.lock().await.drain().map(|(_, s)| s).collect();
+    for server in servers {
+        if let Err(e) = server.kill().await {
+            eprintln!("Failed to kill MCP server {}: {}", server.name, e);
+        }
+    }
+}
+
+fn cursor_params(cursor: Option<String>) -> Option<Value> {
+    cursor.map(|c| json!({ "cursor": c }))
+}
+
+// --- Tauri Commands ---
+
+/// 批准以 command 方式启动的服务器：固定其入口文件 (见 resolve_entry) 的当前内容
+/// 以 script 方式启动的服务器通过 approve_script 批准对应的脚本
+#[tauri::command]
+pub fn mcp_approve_server(app: AppHandle, name: String) -> Result<ScriptApproval, String> {
+    let config = read_server_configs(&app)?
+        .remove(&name)
+        .ok_or_else(|| format!("MCP server '{}' not found in manifest.json", name))?;
+    if config.script.is_some() {
+        return Err(format!(
+            "MCP server '{}' runs a registered script; approve the script instead",
+            name
+        ));
+    }
+    let program = resolve_program(&config.command)?;
+    script_integrity::pin_file(&app, &resolve_entry(&name, &program, &config.args)?)
+}
+
+#[tauri::command]
+pub async fn mcp_start_server(app: AppHandle, name: String) -> Result<McpServerInfo, String> {
+    let server = start_server(&app, &name).await?;
+    let info = server.info.lock().await.clone();
+    info.ok_or_else(|| format!("MCP server '{}' is still initializing", name))
+}
+
+#[tauri::command]
+pub async fn mcp_stop_server(app: AppHandle, name: String) -> Result<(), String> {
+    stop_server(&app, &name).await
+}
+
+#[tauri::command]
+pub async fn mcp_list_servers(state: State<'_, McpHostState>) -> Result<Vec<McpServerInfo>, String> {
+    let servers: Vec<Arc<McpServer>> = state.servers.lock().await.values().cloned().collect();
+    let mut infos = Vec::new();
+    for server in servers {
+        if let Some(info) = server.info.lock().await.clone() {
+            infos.push(info);
+        }
+    }
+    Ok(infos)
+}
+
+#[tauri::command]
+pub async fn mcp_list_tools(
+    state: State<'_, McpHostState>,
+    server: String,
+    cursor: Option<String>,
+) -> Result<Value, String> {
+    get_server(&state, &server)
+        .await?
+        .request("tools/list", cursor_params(cursor))
+        .await
+}
+
+#[tauri::command]
+pub async fn mcp_call_tool(
+    state: State<'_, McpHostState>,
+    server: String,
+    tool: String,
+    arguments: Option<Value>,
+) -> Result<Value, String> {
+    get_server(&state, &server)
+        .await?
+        .request(
+            "tools/call",
+            Some(json!({ "name": tool, "arguments": arguments.unwrap_or_else(|| json!({})) })),
+        )
+        .await
+}
+
+#[tauri::command]
+pub async fn mcp_list_resources(
+    state: State<'_, McpHostState>,
+    server: String,
+    cursor: Option<String>,
+) -> Result<Value, String> {
+    get_server(&state, &server)
+        .await?
+        .request("resources/list", cursor_params(cursor))
+        .await
+}
+
+#[tauri::command]
+pub async fn mcp_read_resource(
+    state: State<'_, McpHostState>,
+    server: String,
+    uri: String,
+) -> Result<Value, String> {
+    get_server(&state, &server)
+        .await?
+        .request("resources/read", Some(json!({ "uri": uri })))
+        .await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Child, ChildStdin, Command, Stdio};
+
+    // 最小的 stdio MCP 服务器：回应 initialize 和 tools/list (先发一条通知)，其余请求返回错误
+    const DUMMY_SERVER: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  [ -z "$id" ] && continue
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","serverInfo":{"name":"dummy"},"capabilities":{"tools":{}}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info"}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo"}]}}\n' "$id" ;;
+    *)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"unknown method"}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    struct TestProcess {
+        stdin: ChildStdin,
+        child: Child,
+    }
+
+    impl ServerProcess for TestProcess {
+        fn write(&mut self, data: &[u8]) -> Result<(), String> {
+            self.stdin
+                .write_all(data)
+                .and_then(|_| self.stdin.flush())
+                .map_err(|e| e.to_string())
+        }
+
+        fn kill(mut self: Box<Self>) -> Result<(), String> {
+            self.child.kill().map_err(|e| e.to_string())?;
+            self.child.wait().map(|_| ()).map_err(|e| e.to_string())
+        }
+    }
+
+    // 启动一个 sh 服务器，并像 start_server 一样在后台读取它的输出
+    fn spawn_server(script: &str) -> Arc<McpServer> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let server = McpServer::new("dummy", Box::new(TestProcess { stdin, child }));
+
+        let reader = server.clone();
+        let runtime = tokio::runtime::Handle::current();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if let Ok(message) = serde_json::from_str(&line) {
+                    runtime.block_on(reader.handle_message(message));
+                }
+            }
+            runtime.block_on(reader.on_exit());
+        });
+        server
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn talks_to_a_stdio_server() {
+        let server = spawn_server(DUMMY_SERVER);
+        let mut notifications = server.notifications.subscribe();
+
+        // 握手完成前，其他调用会等待而不是失败
+        let waiting = tokio::spawn({
+            let server = server.clone();
+            async move { server.wait_ready().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        server.initialize().await.unwrap();
+        waiting.await.unwrap().unwrap();
+        let info = server.info.lock().await.clone().unwrap();
+        assert_eq!(info.protocol_version.as_deref(), Some(PROTOCOL_VERSION));
+        assert_eq!(info.server_info["name"], "dummy");
+
+        let tools = server.request("tools/list", None).await.unwrap();
+        assert_eq!(tools["tools"][0]["name"], "echo");
+        let notification = notifications.recv().await.unwrap();
+        assert_eq!(notification.method, "notifications/message");
+
+        let error = server.request("resources/list", None).await.unwrap_err();
+        assert!(error.contains("-32601"), "{}", error);
+
+        server.kill().await.unwrap();
+        assert!(server.request("tools/list", None).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn waiting_callers_see_a_failed_handshake() {
+        let server = spawn_server("exit 0");
+        let waiting = tokio::spawn({
+            let server = server.clone();
+            async move { server.wait_ready().await }
+        });
+
+        assert!(server.initialize().await.is_err());
+        assert!(waiting.await.unwrap().is_err());
+        assert!(server.wait_ready().await.is_err());
+    }
+
+    #[test]
+    fn entry_file_is_pinned_instead_of_the_runner() {
+        let dir = std::env::temp_dir().join(format!("mcp-entry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = dir.join("server.js");
+        std::fs::write(&server, "// server").unwrap();
+        let node = Path::new("/usr/bin/node");
+
+        let args = vec![
+            "--inspect".to_string(),
+            server.to_string_lossy().to_string(),
+        ];
+        assert_eq!(
+            resolve_entry("fs", node, &args).unwrap(),
+            server.canonicalize().unwrap()
+        );
+
+        let package = vec!["-y".to_string(), "@scope/server".into()];
+        assert!(resolve_entry("fs", Path::new("/usr/bin/npx"), &package).is_err());
+        // 原生的服务器程序没有入口文件参数，固定程序本身
+        assert_eq!(
+            resolve_entry("fs", Path::new("/opt/mcp/server"), &package).unwrap(),
+            Path::new("/opt/mcp/server")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
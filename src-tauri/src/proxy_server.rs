@@ -15,6 +15,7 @@ use tower_http::cors::{CorsLayer, Any};
 use tauri::{AppHandle, Manager};
 use lazy_static::lazy_static;
 use crate::secrets_manager; // 引用你的 secrets_manager 模块
+use crate::secrets_audit::{self, AuditAction, AuditEntry};
 
 lazy_static! {
     static ref SECRET_REGEX: Regex = Regex::new(r"\{\{\s*([a-zA-Z0-9_.-]+)\s*\}\}").unwrap();
@@ -25,6 +26,129 @@ lazy_static! {
 struct ProxyState {
     app: AppHandle,
     client: Client,
+    // 不跟随重定向，用于替换了受限密钥的请求
+    no_redirect_client: Client,
+}
+
+// 拆分规则中的主机和端口："localhost:11434"、"[::1]:8080"、"::1" (不带端口的 IPv6 地址)
+// 端口无法解析时返回 None，此时规则不匹配任何主机
+fn split_host_port(pattern: &str) -> Option<(&str, Option<u16>)> {
+    if let Some(rest) = pattern.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        return match after {
+            "" => Some((host, None)),
+            _ => Some((host, Some(after.strip_prefix(':')?.parse().ok()?))),
+        };
+    }
+    match pattern.rsplit_once(':') {
+        // 主机部分还有冒号说明是 IPv6 地址，最后一段不是端口
+        Some((host, port)) if !host.is_empty() && !host.contains(':') => Some((host, Some(port.parse().ok()?))),
+        _ => Some((pattern, None)),
+    }
+}
+
+// 主机规则：精确匹配、"*.example.com" 匹配子域名、"*" 匹配任意主机，不区分大小写；
+// 规则带端口 (如 "localhost:11434") 时端口也必须一致
+fn host_matches(pattern: &str, host: &str, port: Option<u16>) -> bool {
+    let Some((pattern_host, pattern_port)) = split_host_port(pattern.trim()) else {
+        return false;
+    };
+    if pattern_port.is_some() && pattern_port != port {
+        return false;
+    }
+    if pattern_host == "*" {
+        return true;
+    }
+    // URL 中的 IPv6 地址带有方括号
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+    let pattern_host = pattern_host.to_ascii_lowercase();
+    match pattern_host.strip_prefix("*.") {
+        Some(suffix) => host.len() > suffix.len() && host.ends_with(suffix)
+            && host[..host.len() - suffix.len()].ends_with('.'),
+        None => pattern_host == host,
+    }
+}
+
+// 目标地址的主机 (小写) 和端口；"https://api.openai.com@evil.com/" 的主机是 evil.com
+fn target_host(url: &str) -> Result<(String, Option<u16>), (StatusCode, String)> {
+    let target = url::Url::parse(url)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid X-Forward-To URL".to_string()))?;
+    let host = target.host_str().unwrap_or_default().to_lowercase();
+    Ok((host, target.port_or_known_default()))
+}
+
+// 检查密钥能否发送到该主机 / 用在该 Header 中 (header 为 None 表示出现在 URL 里)
+pub(crate) fn check_secret_access(
+    key: &str,
+    meta: Option<&secrets_manager::SecretMeta>,
+    host: &str,
+    port: Option<u16>,
+    header: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let Some(meta) = meta else { return Ok(()) };
+    if !meta.allowed_hosts.is_empty()
+        && !meta.allowed_hosts.iter().any(|p| host_matches(p, host, port))
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Secret '{}' is not allowed for host '{}'", key, host),
+        ));
+    }
+    if let Some(header) = header {
+        if !meta.allowed_headers.is_empty()
+            && !meta.allowed_headers.iter().any(|h| h.eq_ignore_ascii_case(header))
+        {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Secret '{}' is not allowed in header '{}'", key, header),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// 密钥是否设置了使用限制
+pub(crate) fn is_restricted(meta: Option<&secrets_manager::SecretMeta>) -> bool {
+    meta.is_some_and(|meta| !meta.allowed_hosts.is_empty() || !meta.allowed_headers.is_empty())
+}
+
+// 本次请求的审计信息
+struct AuditContext {
+    host: String,
+    method: String,
+    origin: Option<String>,
+}
+
+impl AuditContext {
+    fn entries<'a>(
+        &'a self,
+        keys: impl IntoIterator<Item = &'a str>,
+        action: AuditAction,
+        status: u16,
+    ) -> Vec<AuditEntry> {
+        keys.into_iter()
+            .map(|key| AuditEntry {
+                host: Some(self.host.clone()),
+                method: Some(self.method.clone()),
+                status: Some(status),
+                origin: self.origin.clone(),
+                ..AuditEntry::new(action, Some(key))
+            })
+            .collect()
+    }
+}
+
+// 字符串中引用的、且确实存在的密钥名
+fn referenced_secrets<'a>(
+    input: &'a str,
+    secrets: &std::collections::HashMap<String, String>,
+) -> Vec<&'a str> {
+    SECRET_REGEX
+        .captures_iter(input)
+        .filter_map(|caps| caps.get(1))
+        .map(|m| m.as_str())
+        .filter(|key| secrets.contains_key(*key))
+        .collect()
 }
 
 // 替换字符串中的 {{KEY}}
@@ -40,9 +164,12 @@ async fn proxy_handler(
     State(state): State<ProxyState>,
     mut req: Request,
 ) -> Result<Response, (StatusCode, String)> {
-    // 1. 获取最新的密钥
-    let secrets = secrets_manager::read_secrets(&state.app)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to load secrets: {}", e)))?;
+    // 1. 获取密钥 (来自内存缓存，文件变化时会自动刷新)
+    let store = secrets_manager::read_store(&state.app).map_err(|e| match e {
+        // 密钥库未解锁时返回 423，前端据此提示用户输入主密码
+        secrets_manager::Error::Locked => (StatusCode::LOCKED, "Secrets are locked".to_string()),
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to load secrets: {}", e)),
+    })?;
 
     // 2. 从 Header 中提取目标 URL (前端传过来的)
     let target_url_header = req.headers_mut().remove("X-Forward-To")
@@ -52,7 +179,33 @@ async fn proxy_handler(
         .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid X-Forward-To header".to_string()))?;
 
     // 3. 替换 URL 中的密钥
-    let final_url = replace_secrets(target_url_str, &secrets);
+    let secrets = &store.secrets;
+    let final_url = replace_secrets(target_url_str, secrets);
+
+    // 按替换后的目标地址检查每个被引用密钥的使用限制，防止插件把密钥转发到其他服务器
+    let (host, port) = target_host(&final_url)?;
+    let origin = ["origin", "referer"]
+        .iter()
+        .find_map(|name| req.headers().get(*name).and_then(|v| v.to_str().ok()))
+        .map(str::to_string);
+    let audit = AuditContext {
+        host: host.clone(),
+        method: req.method().to_string(),
+        origin,
+    };
+
+    // 被拒绝时记录审计日志后返回 403
+    let check = |key: &str, header: Option<&str>| {
+        check_secret_access(key, store.meta.get(key), &host, port, header).map_err(|e| {
+            secrets_audit::record(&state.app, audit.entries([key], AuditAction::Denied, e.0.as_u16()));
+            e
+        })
+    };
+    let mut used: Vec<String> = Vec::new();
+    for key in referenced_secrets(target_url_str, secrets) {
+        check(key, None)?;
+        used.push(key.to_string());
+    }
 
     // 4. 处理 Headers (替换 Authorization 等 Header 中的密钥)
     let mut forward_headers = HeaderMap::new();
@@ -63,7 +216,11 @@ async fn proxy_handler(
         }
 
         if let Ok(val_str) = value.to_str() {
-            let replaced_val = replace_secrets(val_str, &secrets);
+            for key in referenced_secrets(val_str, secrets) {
+                check(key, Some(name.as_str()))?;
+                used.push(key.to_string());
+            }
+            let replaced_val = replace_secrets(val_str, secrets);
             if let Ok(new_val) = HeaderValue::from_str(&replaced_val) {
                 forward_headers.insert(name, new_val);
             }
@@ -74,18 +231,33 @@ async fn proxy_handler(
     }
 
     // 5. 构建并发送请求
+    // 替换了受限密钥时不跟随重定向，否则目标服务器可以把带着密钥的请求重定向到限制之外的主机
+    let client = if used.iter().any(|key| is_restricted(store.meta.get(key))) {
+        &state.no_redirect_client
+    } else {
+        &state.client
+    };
     // 注意：req.into_body() 是流式的，直接转发给 reqwest
-    let reqwest_builder = state.client
+    let reqwest_builder = client
         .request(req.method().clone(), &final_url)
         .headers(forward_headers)
         .body(reqwest::Body::wrap_stream(req.into_body().into_data_stream()));
 
+    used.sort_unstable();
+    used.dedup();
     let remote_res = reqwest_builder.send().await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Request failed: {}", e)))?;
+        .map_err(|e| {
+            let status = StatusCode::BAD_GATEWAY.as_u16();
+            let entries = audit.entries(used.iter().map(String::as_str), AuditAction::Substitute, status);
+            secrets_audit::record(&state.app, entries);
+            (StatusCode::BAD_GATEWAY, format!("Request failed: {}", e))
+        })?;
 
     // 6. 构建响应
     // 将 reqwest 的响应状态码和 Header 转换回 Axum 响应
     let status = remote_res.status();
+    let entries = audit.entries(used.iter().map(String::as_str), AuditAction::Substitute, status.as_u16());
+    secrets_audit::record(&state.app, entries);
     let mut res_builder = Response::builder().status(status);
 
     // 转发响应 Header
@@ -106,9 +278,20 @@ pub fn start_proxy_server(app: AppHandle) -> u16 {
     // 自动寻找一个可用端口
     let port = portpicker::pick_unused_port().unwrap_or(14500);
 
+    let client = Client::builder().build().unwrap();
+    let no_redirect_client = Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    // 后端其他功能 (例如验证密钥) 复用同样的 HTTP Client
+    app.manage(ProxyClient {
+        client: client.clone(),
+        no_redirect: no_redirect_client.clone(),
+    });
     let state = ProxyState {
         app: app.clone(),
-        client: Client::builder().build().unwrap(),
+        client,
+        no_redirect_client,
     };
 
     // 配置最宽松的 CORS
@@ -144,3 +327,125 @@ pub fn get_proxy_port(app: AppHandle) -> u16 {
 }
 
 pub struct ProxyPort(pub std::sync::Mutex<u16>);
+
+pub struct ProxyClient {
+    pub client: Client,
+    // 不跟随重定向，发送受限密钥时使用
+    pub no_redirect: Client,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(hosts: &[&str], headers: &[&str]) -> secrets_manager::SecretMeta {
+        secrets_manager::SecretMeta {
+            allowed_hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            allowed_headers: headers.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exact_and_wildcard_hosts() {
+        assert!(host_matches("api.openai.com", "api.openai.com", Some(443)));
+        assert!(host_matches("API.OpenAI.com", "api.openai.com", Some(443)));
+        assert!(!host_matches(
+            "api.openai.com",
+            "api.openai.com.evil.com",
+            Some(443)
+        ));
+        assert!(!host_matches("openai.com", "api.openai.com", Some(443)));
+
+        assert!(host_matches(
+            "*.openai.azure.com",
+            "res.openai.azure.com",
+            Some(443)
+        ));
+        assert!(host_matches(
+            "*.openai.azure.com",
+            "a.b.openai.azure.com",
+            Some(443)
+        ));
+        assert!(!host_matches(
+            "*.openai.azure.com",
+            "openai.azure.com",
+            Some(443)
+        ));
+        assert!(!host_matches(
+            "*.openai.azure.com",
+            "evilopenai.azure.com",
+            Some(443)
+        ));
+
+        assert!(host_matches("*", "anything.example", None));
+        assert!(host_matches("*:8080", "anything.example", Some(8080)));
+        assert!(!host_matches("*:8080", "anything.example", Some(80)));
+    }
+
+    #[test]
+    fn port_qualified_hosts() {
+        assert!(host_matches("localhost:11434", "localhost", Some(11434)));
+        assert!(!host_matches("localhost:11434", "localhost", Some(80)));
+        assert!(host_matches("localhost", "localhost", Some(11434)));
+        // 无法解析的端口不匹配任何地址
+        assert!(!host_matches("localhost:abc", "localhost", Some(80)));
+        assert!(!host_matches("localhost:99999", "localhost", Some(80)));
+    }
+
+    #[test]
+    fn ipv6_hosts() {
+        let (host, port) = target_host("http://[::1]:8080/v1").unwrap();
+        assert_eq!((host.as_str(), port), ("[::1]", Some(8080)));
+
+        assert!(host_matches("[::1]:8080", &host, port));
+        assert!(host_matches("[::1]", &host, port));
+        assert!(host_matches("::1", &host, port));
+        assert!(!host_matches("[::1]:9090", &host, port));
+        assert!(!host_matches("[::2]", &host, port));
+        assert!(!host_matches("[::1", &host, port));
+    }
+
+    #[test]
+    fn userinfo_does_not_count_as_host() {
+        let (host, port) = target_host("https://api.openai.com@evil.example/v1").unwrap();
+        assert_eq!((host.as_str(), port), ("evil.example", Some(443)));
+
+        let restricted = meta(&["api.openai.com"], &[]);
+        assert!(check_secret_access("KEY", Some(&restricted), &host, port, None).is_err());
+        assert!(target_host("not a url").is_err());
+    }
+
+    #[test]
+    fn secret_access_checks_hosts_and_headers() {
+        let (host, port) = target_host("https://api.openai.com/v1/models").unwrap();
+        assert!(check_secret_access("KEY", None, &host, port, Some("authorization")).is_ok());
+        assert!(
+            check_secret_access("KEY", Some(&meta(&[], &[])), &host, port, Some("x-any")).is_ok()
+        );
+
+        let restricted = meta(&["api.openai.com"], &["Authorization"]);
+        assert!(
+            check_secret_access("KEY", Some(&restricted), &host, port, Some("authorization"))
+                .is_ok()
+        );
+        // header 为 None 表示出现在 URL 中，只检查主机
+        assert!(check_secret_access("KEY", Some(&restricted), &host, port, None).is_ok());
+        let (status, message) =
+            check_secret_access("KEY", Some(&restricted), &host, port, Some("x-api-key"))
+                .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(message.contains("x-api-key"));
+        assert!(
+            check_secret_access("KEY", Some(&restricted), "api.anthropic.com", port, None).is_err()
+        );
+    }
+
+    #[test]
+    fn restricted_only_with_hosts_or_headers() {
+        assert!(!is_restricted(None));
+        assert!(!is_restricted(Some(&meta(&[], &[]))));
+        assert!(is_restricted(Some(&meta(&["api.openai.com"], &[]))));
+        assert!(is_restricted(Some(&meta(&[], &["authorization"]))));
+    }
+}
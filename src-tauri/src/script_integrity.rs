@@ -0,0 +1,216 @@
+// src-tauri/src/script_integrity.rs
+
+use crate::script_manager;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+// 前端可根据错误前缀区分 "未批准" 和 "内容已变化 (需要重新确认)"
+pub const UNAPPROVED_PREFIX: &str = "SCRIPT_UNAPPROVED";
+pub const MODIFIED_PREFIX: &str = "SCRIPT_MODIFIED";
+
+// 用户批准某个脚本时记录的信息
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptApproval {
+    pub path: String,
+    pub sha256: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub approved_at: chrono::DateTime<Utc>,
+}
+
+// 脚本当前的完整性状态
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptIntegrityStatus {
+    pub path: String,
+    pub status: String, // "approved" | "unapproved" | "modified"
+    pub current_sha256: String,
+    pub approved_sha256: Option<String>,
+}
+
+// --- 辅助函数 ---
+
+// 获取 script_approvals.json 的路径
+fn get_approvals_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("script_approvals.json"))
+}
+
+// 读取批准列表，Key 为规范化后的脚本路径
+fn read_approvals(app: &AppHandle) -> Result<HashMap<String, ScriptApproval>, String> {
+    let path = get_approvals_path(app)?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid script_approvals.json: {}", e))
+}
+
+fn write_approvals(
+    app: &AppHandle,
+    approvals: &HashMap<String, ScriptApproval>,
+) -> Result<(), String> {
+    let path = get_approvals_path(app)?;
+    let contents = serde_json::to_string_pretty(approvals).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+// 计算文件内容的 SHA-256
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn canonicalize(path: &str) -> Result<PathBuf, String> {
+    PathBuf::from(path)
+        .canonicalize()
+        .map_err(|_| format!("Script not found at path: {}", path))
+}
+
+// 根据批准列表判断脚本的完整性状态
+fn status_of(
+    approvals: &HashMap<String, ScriptApproval>,
+    canonical: &Path,
+) -> Result<ScriptIntegrityStatus, String> {
+    let key = canonical.to_string_lossy().to_string();
+    let current = hash_file(canonical)?;
+    let approved = approvals.get(&key).map(|a| a.sha256.clone());
+
+    let status = match &approved {
+        None => "unapproved",
+        Some(hash) if hash == &current => "approved",
+        Some(_) => "modified",
+    };
+
+    Ok(ScriptIntegrityStatus {
+        path: key,
+        status: status.into(),
+        current_sha256: current,
+        approved_sha256: approved,
+    })
+}
+
+// 未批准或内容已变化时返回带前缀的错误
+fn check_status(status: &ScriptIntegrityStatus) -> Result<(), String> {
+    match status.status.as_str() {
+        "approved" => Ok(()),
+        "modified" => Err(format!(
+            "{}: Script content changed since it was approved, reconfirmation required: {}",
+            MODIFIED_PREFIX, status.path
+        )),
+        _ => Err(format!(
+            "{}: Script has not been approved: {}",
+            UNAPPROVED_PREFIX, status.path
+        )),
+    }
+}
+
+/// 在启动脚本前调用：脚本未被批准或内容与批准时不一致时返回带前缀的错误
+pub fn verify_script(app: &AppHandle, canonical: &Path) -> Result<(), String> {
+    check_status(&status_of(&read_approvals(app)?, canonical)?)
+}
+
+/// 记录文件当前内容的哈希；调用方负责确认 canonical 是允许运行的文件
+/// (approve_script 要求位于 executable 目录，MCP 服务器由 manifest 配置解析出入口文件)
+pub(crate) fn pin_file(app: &AppHandle, canonical: &Path) -> Result<ScriptApproval, String> {
+    let key = canonical.to_string_lossy().to_string();
+    let approval = ScriptApproval {
+        path: key.clone(),
+        sha256: hash_file(canonical)?,
+        approved_at: Utc::now(),
+    };
+
+    let mut approvals = read_approvals(app)?;
+    approvals.insert(key, approval.clone());
+    write_approvals(app, &approvals)?;
+    Ok(approval)
+}
+
+// --- Tauri Commands ---
+
+// 批准脚本的当前内容；与启动脚本相同，只接受 executable 目录中的 .sh / .bat 文件
+#[tauri::command]
+pub fn approve_script(app: AppHandle, path: String) -> Result<ScriptApproval, String> {
+    let canonical = script_manager::validate_script_path(&app, &path)?;
+    pin_file(&app, &canonical)
+}
+
+#[tauri::command]
+pub fn revoke_script_approval(app: AppHandle, path: String) -> Result<(), String> {
+    // 脚本可能已被删除，此时直接使用传入的路径作为 Key
+    let key = canonicalize(&path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(path);
+
+    let mut approvals = read_approvals(&app)?;
+    if approvals.remove(&key).is_some() {
+        write_approvals(&app, &approvals)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_script_approvals(app: AppHandle) -> Result<Vec<ScriptApproval>, String> {
+    let mut approvals: Vec<ScriptApproval> = read_approvals(&app)?.into_values().collect();
+    approvals.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(approvals)
+}
+
+#[tauri::command]
+pub fn get_script_integrity(app: AppHandle, path: String) -> Result<ScriptIntegrityStatus, String> {
+    let canonical = canonicalize(&path)?;
+    status_of(&read_approvals(&app)?, &canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approval(path: &Path, sha256: String) -> HashMap<String, ScriptApproval> {
+        let key = path.to_string_lossy().to_string();
+        let approval = ScriptApproval {
+            path: key.clone(),
+            sha256,
+            approved_at: Utc::now(),
+        };
+        HashMap::from([(key, approval)])
+    }
+
+    #[test]
+    fn unapproved_and_modified_scripts_are_refused() {
+        let dir = std::env::temp_dir().join(format!("script-integrity-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("job.sh");
+        fs::write(&script, "echo hello\n").unwrap();
+
+        let err = check_status(&status_of(&HashMap::new(), &script).unwrap()).unwrap_err();
+        assert!(err.starts_with(UNAPPROVED_PREFIX), "{}", err);
+
+        let approvals = approval(&script, hash_file(&script).unwrap());
+        let status = status_of(&approvals, &script).unwrap();
+        assert_eq!(status.status, "approved");
+        assert!(check_status(&status).is_ok());
+
+        fs::write(&script, "echo changed\n").unwrap();
+        let status = status_of(&approvals, &script).unwrap();
+        assert_eq!(status.status, "modified");
+        assert_ne!(
+            status.approved_sha256.as_ref(),
+            Some(&status.current_sha256)
+        );
+        let err = check_status(&status).unwrap_err();
+        assert!(err.starts_with(MODIFIED_PREFIX), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,10 +2,12 @@
 
 use std::collections::HashMap;
 use std::path::{PathBuf};
+use crate::{script_integrity, script_protocol, script_registry, script_sandbox};
+use regex::Regex;
 use tauri::{AppHandle, Manager, State, Emitter};
 use tauri_plugin_shell::process::{CommandChild, CommandEvent};
 use tauri_plugin_shell::ShellExt;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
 
 // 用于管理所有脚本子进程的状态结构
 pub struct ScriptProcessState {
@@ -29,7 +31,7 @@ struct ScriptTerminated {
 }
 
 // 验证并规范化脚本路径 (This function is unchanged)
-fn validate_script_path(app: &AppHandle, path_str: &str) -> Result<PathBuf, String> {
+pub(crate) fn validate_script_path(app: &AppHandle, path_str: &str) -> Result<PathBuf, String> {
     // 1. 检查文件后缀
     if !path_str.ends_with(".sh") && !path_str.ends_with(".bat") {
         return Err("Invalid script type. Only .sh and .bat files are allowed.".into());
@@ -55,28 +57,129 @@ fn validate_script_path(app: &AppHandle, path_str: &str) -> Result<PathBuf, Stri
     Ok(canonical_path)
 }
 
-#[tauri::command]
-pub async fn execute_script(
-    app: AppHandle,
-    state: State<'_, ScriptProcessState>,
-    path: String,
-) -> Result<(), String> {
-    let script_path = validate_script_path(&app, &path)?;
+// 就绪信号：当脚本输出的某一行匹配 pattern 时，通过 tx 通知等待方
+// 如果脚本在匹配前退出，tx 会被丢弃，等待方会收到错误
+pub(crate) struct ReadySignal {
+    pub pattern: Regex,
+    pub tx: oneshot::Sender<()>,
+}
+
+// 脚本结束时的信息，output 为捕获到的输出 (超出上限时只保留末尾部分)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ScriptExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub output: String,
+    pub output_truncated: bool,
+}
+
+// 捕获输出的上限 (字节)
+const MAX_CAPTURED_OUTPUT: usize = 16 * 1024;
+
+// 启动脚本时可选的回调：就绪信号、结束通知 (同时捕获输出)
+#[derive(Default)]
+pub(crate) struct SpawnHooks {
+    pub ready: Option<ReadySignal>,
+    pub exit: Option<oneshot::Sender<ScriptExit>>,
+}
+
+// 将一行输出追加到捕获缓冲区，超出上限时丢弃最早的内容
+fn capture_line(exit: &mut ScriptExit, line: &str) {
+    exit.output.push_str(line.trim_end_matches('\n'));
+    exit.output.push('\n');
+    if exit.output.len() > MAX_CAPTURED_OUTPUT {
+        let mut cut = exit.output.len() - MAX_CAPTURED_OUTPUT;
+        while !exit.output.is_char_boundary(cut) {
+            cut += 1;
+        }
+        exit.output.drain(..cut);
+        exit.output_truncated = true;
+    }
+}
+
+// 检查一行输出是否满足就绪条件，满足则发送信号（只发送一次）
+fn check_ready(ready: &mut Option<ReadySignal>, line: &str) {
+    let matched = ready
+        .as_ref()
+        .map(|r| r.pattern.is_match(line))
+        .unwrap_or(false);
+    if matched {
+        if let Some(r) = ready.take() {
+            let _ = r.tx.send(());
+        }
+    }
+}
+
+// 已通过路径和完整性校验、并按注册信息应用了资源限制和受限模式的启动命令
+pub(crate) struct PreparedScript {
+    // 规范化后的脚本路径，即 children 中使用的 Key
+    pub path: String,
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub protocol: bool,
+}
+
+/// 脚本可写的 scratch 目录 (<app_data_dir>/script_scratch/<name>)
+pub(crate) fn scratch_dir(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("script_scratch")
+        .join(name))
+}
+
+// 启动脚本前的检查和命令构建，供 spawn_script 和 MCP 服务器 (mcp_host) 共用
+pub(crate) fn prepare_script(app: &AppHandle, path: &str) -> Result<PreparedScript, String> {
+    let script_path = validate_script_path(app, path)?;
     let script_path_str = script_path.to_str().unwrap().to_string();
 
+    // 只运行用户批准过、且内容未改变的脚本
+    script_integrity::verify_script(app, &script_path)?;
+
+    // 根据注册信息应用资源限制和受限模式；未注册的脚本按原方式启动
+    let entry = script_registry::find_by_path(app, &script_path);
+    let scratch_name = entry
+        .as_ref()
+        .map(|e| e.name.clone())
+        .or_else(|| script_path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let (cmd, args) = script_sandbox::build_command(
+        &script_path_str,
+        entry.as_ref().and_then(|e| e.limits.as_ref()),
+        entry.as_ref().map(|e| e.restricted).unwrap_or(false),
+        &scratch_dir(app, &scratch_name)?,
+    )?;
+
+    Ok(PreparedScript {
+        path: script_path_str,
+        cmd,
+        args,
+        protocol: entry.map(|e| e.protocol).unwrap_or(false),
+    })
+}
+
+// 启动脚本的内部实现，供 execute_script、依赖编排 (script_registry) 和定时任务 (script_scheduler) 共用
+// 返回规范化后的脚本路径，即 children 中使用的 Key
+pub(crate) async fn spawn_script(
+    app: &AppHandle,
+    path: &str,
+    hooks: SpawnHooks,
+) -> Result<String, String> {
+    let PreparedScript {
+        path: script_path_str,
+        cmd,
+        args,
+        protocol,
+    } = prepare_script(app, path)?;
+
+    let state = app.state::<ScriptProcessState>();
     let mut children = state.children.lock().await;
 
     if children.contains_key(&script_path_str) {
         return Err("Script is already running.".into());
     }
 
-    // 根据操作系统选择解释器
-    let (cmd, args) = if cfg!(windows) {
-        ("cmd", vec!["/C", &script_path_str])
-    } else {
-        ("sh", vec!["-c", &script_path_str])
-    };
-
     let (mut rx, child) = app.shell() // Use the ShellExt trait on AppHandle
         .command(cmd)
         .args(args)
@@ -88,30 +191,49 @@ pub async fn execute_script(
 
     let app_clone = app.clone();
     let path_clone = script_path_str.clone();
+    let SpawnHooks { mut ready, exit: exit_tx } = hooks;
+    let capture = exit_tx.is_some();
+    let mut exit = ScriptExit::default();
 
     // 异步监听脚本输出
     tauri::async_runtime::spawn(async move {
         while let Some(event) = rx.recv().await {
             match event {
                 CommandEvent::Stdout(line) => {
+                    let line = String::from_utf8_lossy(&line).to_string();
+                    check_ready(&mut ready, &line);
+                    if capture {
+                        capture_line(&mut exit, &line);
+                    }
+                    // 结构化消息单独转发，不再作为普通输出
+                    if protocol && script_protocol::handle_line(&app_clone, &path_clone, &line) {
+                        continue;
+                    }
                     app_clone
                         .emit("script-output", ScriptOutput {
                             path: path_clone.clone(),
-                            line: String::from_utf8_lossy(&line).to_string(),
+                            line,
                             stream: "stdout".into(),
                         })
                         .unwrap();
                 }
                 CommandEvent::Stderr(line) => {
+                    let line = String::from_utf8_lossy(&line).to_string();
+                    check_ready(&mut ready, &line);
+                    if capture {
+                        capture_line(&mut exit, &line);
+                    }
                     app_clone
                         .emit("script-output", ScriptOutput {
                             path: path_clone.clone(),
-                            line: String::from_utf8_lossy(&line).to_string(),
+                            line,
                             stream: "stderr".into(),
                         })
                         .unwrap();
                 }
                 CommandEvent::Terminated(payload) => {
+                    exit.code = payload.code;
+                    exit.signal = payload.signal;
                     app_clone
                         .emit("script-terminated", ScriptTerminated {
                             path: path_clone.clone(),
@@ -122,6 +244,9 @@ pub async fn execute_script(
                     break; // 进程结束，退出监听循环
                 }
                 CommandEvent::Error(e) => {
+                    if capture {
+                        capture_line(&mut exit, &e);
+                    }
                      app_clone
                         .emit("script-output", ScriptOutput {
                             path: path_clone.clone(),
@@ -139,20 +264,37 @@ pub async fn execute_script(
 
         let mut children = script_state.children.lock().await;
         children.remove(&path_clone);
+        drop(children);
+        app_clone
+            .state::<script_protocol::PendingSecretRequests>()
+            .clear_script(&path_clone);
         println!("[Script Manager] Cleaned up terminated process: {}", path_clone);
+
+        // 在进程从状态中移除之后再通知，保证等待方看到的状态是一致的
+        if let Some(tx) = exit_tx {
+            let _ = tx.send(exit);
+        }
     });
 
-    Ok(())
+    Ok(script_path_str)
 }
 
-#[tauri::command]
-pub async fn shutdown_script(
-    state: State<'_, ScriptProcessState>,
-    path: String,
-) -> Result<(), String> {
+// 检查脚本是否正在运行（path 会先被规范化）
+pub(crate) async fn is_script_running(app: &AppHandle, path: &str) -> bool {
+    let script_path = match validate_script_path(app, path) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    let state = app.state::<ScriptProcessState>();
+    let children = state.children.lock().await;
+    children.contains_key(script_path.to_str().unwrap_or_default())
+}
+
+// 结束脚本进程的内部实现，path 需为 children 中的 Key
+pub(crate) async fn kill_script(state: &ScriptProcessState, path: &str) -> Result<(), String> {
     let mut children = state.children.lock().await;
 
-    if let Some(child) = children.remove(&path) {
+    if let Some(child) = children.remove(path) {
         child.kill().map_err(|e| e.to_string())?;
         println!("Successfully shut down script: {}", path);
         Ok(())
@@ -160,3 +302,17 @@ pub async fn shutdown_script(
         Err("Script not found or not running.".into())
     }
 }
+
+#[tauri::command]
+pub async fn execute_script(app: AppHandle, path: String) -> Result<(), String> {
+    spawn_script(&app, &path, SpawnHooks::default()).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn shutdown_script(
+    state: State<'_, ScriptProcessState>,
+    path: String,
+) -> Result<(), String> {
+    kill_script(&state, &path).await
+}
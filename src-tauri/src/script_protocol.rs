@@ -0,0 +1,377 @@
+// src-tauri/src/script_protocol.rs
+
+use crate::proxy_server;
+use crate::script_manager::ScriptProcessState;
+use crate::secrets_audit::{self, AuditAction, AuditEntry};
+use crate::secrets_manager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use zeroize::Zeroizing;
+
+// 结构化消息的两种写法 (脚本需在注册信息中开启 protocol)：
+// 1. 前缀形式:   @@pulsar {"type":"progress","value":0.5}
+// 2. JSON 信封:  {"$pulsar":{"type":"progress","value":0.5}}
+pub const PROTOCOL_PREFIX: &str = "@@pulsar ";
+const ENVELOPE_KEY: &str = "$pulsar";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+// 脚本可以发送的消息类型
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", rename_all_fields = "camelCase")]
+pub enum ScriptMessage {
+    // value 为 0.0..=1.0 的比例，或者使用 current/total
+    Progress {
+        value: Option<f64>,
+        current: Option<u64>,
+        total: Option<u64>,
+        message: Option<String>,
+    },
+    Notification {
+        title: String,
+        body: Option<String>,
+    },
+    SetStatus {
+        status: String,
+    },
+    // 请求一个密钥，前端确认后通过 respond_script_secret 写回脚本的 stdin
+    RequestSecret {
+        id: String,
+        key: String,
+        reason: Option<String>,
+    },
+    Log {
+        level: LogLevel,
+        message: String,
+    },
+}
+
+impl ScriptMessage {
+    // 每种消息对应的 Tauri 事件名
+    fn event_name(&self) -> &'static str {
+        match self {
+            ScriptMessage::Progress { .. } => "script-progress",
+            ScriptMessage::Notification { .. } => "script-notification",
+            ScriptMessage::SetStatus { .. } => "script-status",
+            ScriptMessage::RequestSecret { .. } => "script-secret-request",
+            ScriptMessage::Log { .. } => "script-log",
+        }
+    }
+}
+
+// 脚本发出、尚未回应的密钥请求：(脚本路径, 请求 id) -> 密钥名
+// 只有脚本确实请求过的密钥才能写回，且每个请求只能回应一次
+#[derive(Default)]
+pub struct PendingSecretRequests {
+    pending: Mutex<HashMap<(String, String), String>>,
+}
+
+impl PendingSecretRequests {
+    fn insert(&self, path: &str, id: &str, key: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .insert((path.to_string(), id.to_string()), key.to_string());
+    }
+
+    // 检查回应是否与请求匹配，不消耗请求
+    fn check(&self, path: &str, id: &str, key: &str) -> Result<(), String> {
+        let pending = self.pending.lock().unwrap();
+        match pending.get(&(path.to_string(), id.to_string())) {
+            Some(requested) if requested == key => Ok(()),
+            Some(requested) => Err(format!(
+                "Secret request '{}' asked for '{}', not '{}'",
+                id, requested, key
+            )),
+            None => Err(format!("No pending secret request '{}' from this script", id)),
+        }
+    }
+
+    // 取出与回应匹配的请求；不匹配时返回错误，且不会消耗已有的请求
+    fn take(&self, path: &str, id: &str, key: &str) -> Result<(), String> {
+        self.check(path, id, key)?;
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(&(path.to_string(), id.to_string()));
+        Ok(())
+    }
+
+    /// 脚本退出时丢弃它未被回应的请求
+    pub fn clear_script(&self, path: &str) {
+        self.pending.lock().unwrap().retain(|(p, _), _| p != path);
+    }
+}
+
+// 写回脚本 stdin 的密钥回应，value 与 error 只有一个
+#[derive(Debug, Serialize)]
+struct SecretResponse<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: &'a str,
+    key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+// 决定如何回应密钥请求，返回回应和需要记录的审计动作
+// 设有使用限制的密钥交给脚本后就无法再限制它的去向，只有用户明确允许 (allow_restricted) 时才交出
+fn secret_response<'a>(
+    id: &'a str,
+    key: &'a str,
+    value: Option<&'a str>,
+    restricted: bool,
+    granted: bool,
+    allow_restricted: bool,
+) -> (SecretResponse<'a>, Option<AuditAction>) {
+    let respond = |value, error| SecretResponse {
+        kind: "secret",
+        id,
+        key,
+        value,
+        error,
+    };
+    match value {
+        _ if !granted => (respond(None, Some("denied")), Some(AuditAction::Denied)),
+        None => (respond(None, Some("not_found")), None),
+        Some(_) if restricted && !allow_restricted => {
+            (respond(None, Some("restricted")), Some(AuditAction::Denied))
+        }
+        Some(value) if restricted => (respond(Some(value), None), Some(AuditAction::ScriptGrantRestricted)),
+        Some(value) => (respond(Some(value), None), Some(AuditAction::ScriptGrant)),
+    }
+}
+
+// 发送到前端的事件载荷
+#[derive(Clone, Serialize)]
+struct ScriptProtocolEvent<'a> {
+    path: &'a str,
+    #[serde(flatten)]
+    message: &'a ScriptMessage,
+}
+
+/// 解析一行输出
+/// 不是协议消息时返回 None，格式错误时返回 Some(Err)
+pub fn parse_line(line: &str) -> Option<Result<ScriptMessage, String>> {
+    let trimmed = line.trim();
+
+    if let Some(rest) = trimmed.strip_prefix(PROTOCOL_PREFIX) {
+        return Some(serde_json::from_str(rest.trim()).map_err(|e| e.to_string()));
+    }
+
+    // 只有包含 $pulsar 键的 JSON 对象才会被当作信封解析，避免误伤普通 JSON 输出
+    if trimmed.starts_with('{') && trimmed.contains(ENVELOPE_KEY) {
+        if let Ok(serde_json::Value::Object(mut map)) = serde_json::from_str(trimmed) {
+            if let Some(inner) = map.remove(ENVELOPE_KEY) {
+                return Some(serde_json::from_value(inner).map_err(|e| e.to_string()));
+            }
+        }
+    }
+
+    None
+}
+
+/// 处理一行脚本输出，是协议消息时转发为对应事件并返回 true
+pub fn handle_line(app: &AppHandle, path: &str, line: &str) -> bool {
+    match parse_line(line) {
+        None => false,
+        Some(Ok(message)) => {
+            if let ScriptMessage::RequestSecret { id, key, .. } = &message {
+                app.state::<PendingSecretRequests>().insert(path, id, key);
+            }
+            let _ = app.emit(
+                message.event_name(),
+                ScriptProtocolEvent {
+                    path,
+                    message: &message,
+                },
+            );
+            true
+        }
+        Some(Err(e)) => {
+            let message = ScriptMessage::Log {
+                level: LogLevel::Warn,
+                message: format!("Malformed protocol message ({}): {}", e, line.trim()),
+            };
+            let _ = app.emit(
+                message.event_name(),
+                ScriptProtocolEvent {
+                    path,
+                    message: &message,
+                },
+            );
+            true
+        }
+    }
+}
+
+// --- Tauri Commands ---
+
+// 回应脚本的 request-secret 消息，将结果以一行 JSON 写入脚本的 stdin
+// 只能回应脚本发出过的请求 (id 与密钥名都需一致)，每个请求只能回应一次；写入失败时请求仍然保留
+// 设有使用限制的密钥默认拒绝，allow_restricted 为 true 时才交给脚本，并单独记录审计日志
+#[tauri::command]
+pub async fn respond_script_secret(
+    app: AppHandle,
+    state: State<'_, ScriptProcessState>,
+    path: String,
+    id: String,
+    key: String,
+    granted: bool,
+    allow_restricted: Option<bool>,
+) -> Result<(), String> {
+    let pending = app.state::<PendingSecretRequests>();
+    // 持有进程表的锁直到写入完成，同一请求的并发回应会依次检查
+    let mut children = state.children.lock().await;
+    let child = children
+        .get_mut(&path)
+        .ok_or_else(|| "Script not found or not running.".to_string())?;
+    pending.check(&path, &id, &key)?;
+
+    let store = if granted {
+        Some(secrets_manager::read_store(&app).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let value = store.as_ref().and_then(|s| s.secrets.get(&key)).map(String::as_str);
+    let restricted = store
+        .as_ref()
+        .is_some_and(|s| proxy_server::is_restricted(s.meta.get(&key)));
+    let (response, action) = secret_response(
+        &id,
+        &key,
+        value,
+        restricted,
+        granted,
+        allow_restricted.unwrap_or(false),
+    );
+
+    // 这一行包含密钥值，写入后清零
+    let mut line = Zeroizing::new(serde_json::to_string(&response).map_err(|e| e.to_string())?);
+    line.push('\n');
+    child.write(line.as_bytes()).map_err(|e| e.to_string())?;
+    pending.take(&path, &id, &key)?;
+    drop(children);
+
+    if let Some(action) = action {
+        secrets_audit::record(
+            &app,
+            vec![AuditEntry {
+                origin: Some(path),
+                ..AuditEntry::new(action, Some(&key))
+            }],
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prefixed_and_envelope_messages() {
+        let prefixed = parse_line(r#"@@pulsar {"type":"set-status","status":"busy"}"#);
+        assert!(
+            matches!(prefixed, Some(Ok(ScriptMessage::SetStatus { status })) if status == "busy")
+        );
+
+        let envelope =
+            parse_line(r#"{"$pulsar":{"type":"request-secret","id":"1","key":"TOKEN"}}"#);
+        assert!(matches!(
+            envelope,
+            Some(Ok(ScriptMessage::RequestSecret { id, key, reason: None })) if id == "1" && key == "TOKEN"
+        ));
+    }
+
+    #[test]
+    fn ignores_plain_output_and_reports_malformed_messages() {
+        assert!(parse_line("hello").is_none());
+        assert!(parse_line(r#"{"value":1}"#).is_none());
+        assert!(matches!(parse_line("@@pulsar {not json"), Some(Err(_))));
+        assert!(matches!(
+            parse_line(r#"@@pulsar {"type":"unknown"}"#),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn secret_responses_must_match_a_pending_request() {
+        let pending = PendingSecretRequests::default();
+        assert!(pending.take("/a.sh", "1", "TOKEN").is_err());
+
+        pending.insert("/a.sh", "1", "TOKEN");
+        // 密钥名不一致、或来自其他脚本的回应都会被拒绝，且不消耗请求
+        assert!(pending.take("/a.sh", "1", "OTHER").is_err());
+        assert!(pending.take("/b.sh", "1", "TOKEN").is_err());
+        assert!(pending.take("/a.sh", "1", "TOKEN").is_ok());
+        // 每个请求只能回应一次
+        assert!(pending.take("/a.sh", "1", "TOKEN").is_err());
+    }
+
+    #[test]
+    fn clearing_a_script_drops_only_its_requests() {
+        let pending = PendingSecretRequests::default();
+        pending.insert("/a.sh", "1", "TOKEN");
+        pending.insert("/b.sh", "1", "TOKEN");
+        pending.clear_script("/a.sh");
+        assert!(pending.take("/a.sh", "1", "TOKEN").is_err());
+        assert!(pending.take("/b.sh", "1", "TOKEN").is_ok());
+    }
+
+    #[test]
+    fn restricted_secrets_need_an_explicit_override() {
+        let to_json = |(response, action): (SecretResponse, Option<AuditAction>)| {
+            (serde_json::to_value(&response).unwrap(), action)
+        };
+
+        let (json, action) = to_json(secret_response("1", "TOKEN", Some("v"), false, true, false));
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "secret", "id": "1", "key": "TOKEN", "value": "v" })
+        );
+        assert_eq!(action, Some(AuditAction::ScriptGrant));
+
+        let (json, action) = to_json(secret_response("1", "TOKEN", Some("v"), true, true, false));
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "secret", "id": "1", "key": "TOKEN", "error": "restricted" })
+        );
+        assert_eq!(action, Some(AuditAction::Denied));
+
+        let (json, action) = to_json(secret_response("1", "TOKEN", Some("v"), true, true, true));
+        assert_eq!(json["value"], "v");
+        assert_eq!(action, Some(AuditAction::ScriptGrantRestricted));
+
+        let (json, action) = to_json(secret_response("1", "TOKEN", Some("v"), false, false, true));
+        assert_eq!(json["error"], "denied");
+        assert!(json.get("value").is_none());
+        assert_eq!(action, Some(AuditAction::Denied));
+
+        let (json, action) = to_json(secret_response("1", "MISSING", None, false, true, false));
+        assert_eq!(json["error"], "not_found");
+        assert_eq!(action, None);
+    }
+
+    #[test]
+    fn checking_a_request_does_not_consume_it() {
+        let pending = PendingSecretRequests::default();
+        pending.insert("/a.sh", "1", "TOKEN");
+        assert!(pending.check("/a.sh", "1", "TOKEN").is_ok());
+        assert!(pending.check("/a.sh", "1", "TOKEN").is_ok());
+        assert!(pending.take("/a.sh", "1", "TOKEN").is_ok());
+        assert!(pending.check("/a.sh", "1", "TOKEN").is_err());
+    }
+}
@@ -0,0 +1,424 @@
+// src-tauri/src/script_registry.rs
+
+use crate::script_manager::{self, ReadySignal, ScriptProcessState, SpawnHooks};
+use crate::script_sandbox::ResourceLimits;
+use crate::script_scheduler;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+
+// 默认的就绪等待超时时间 (毫秒)
+fn default_ready_timeout() -> u64 {
+    30_000
+}
+
+// 脚本的就绪检查方式
+// - output: 脚本输出 (stdout/stderr) 中出现匹配 pattern 的行
+// - port:   本地端口可以建立 TCP 连接
+// - delay:  启动后固定等待一段时间
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ReadinessCheck {
+    Output {
+        pattern: String,
+        #[serde(default = "default_ready_timeout")]
+        timeout_ms: u64,
+    },
+    Port {
+        port: u16,
+        #[serde(default = "default_ready_timeout")]
+        timeout_ms: u64,
+    },
+    Delay {
+        ms: u64,
+    },
+}
+
+// 已注册的脚本，name 作为依赖关系中的标识
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptEntry {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub ready: Option<ReadinessCheck>,
+    // 资源限制 (rlimit / cgroup v2 / nice)
+    #[serde(default)]
+    pub limits: Option<ResourceLimits>,
+    // 受限模式：无网络、文件系统只读 (仅 scratch 目录可写)
+    #[serde(default)]
+    pub restricted: bool,
+    // 开启后 stdout 中的结构化消息 (见 script_protocol) 会被解析为独立事件
+    #[serde(default)]
+    pub protocol: bool,
+}
+
+// "start all" 中每个脚本的启动结果
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStartReport {
+    pub name: String,
+    pub status: String, // "started" | "already_running" | "failed" | "skipped"
+    pub error: Option<String>,
+}
+
+// --- 辅助函数 ---
+
+// 获取 scripts.json 的路径 (<app_data_dir>/scripts.json)
+fn get_registry_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("scripts.json"))
+}
+
+pub fn read_registry(app: &AppHandle) -> Result<Vec<ScriptEntry>, String> {
+    let path = get_registry_path(app)?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid scripts.json: {}", e))
+}
+
+// 根据规范化后的脚本路径查找注册信息
+pub fn find_by_path(app: &AppHandle, canonical_path: &Path) -> Option<ScriptEntry> {
+    read_registry(app).ok()?.into_iter().find(|e| {
+        PathBuf::from(&e.path)
+            .canonicalize()
+            .map(|p| p == canonical_path)
+            .unwrap_or(false)
+    })
+}
+
+fn write_registry(app: &AppHandle, entries: &[ScriptEntry]) -> Result<(), String> {
+    let path = get_registry_path(app)?;
+    let contents = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+// 按依赖关系进行拓扑排序 (Kahn 算法)
+// 依赖不存在或存在循环依赖时返回错误
+fn topo_sort(entries: &[ScriptEntry]) -> Result<Vec<ScriptEntry>, String> {
+    let by_name: HashMap<&str, &ScriptEntry> =
+        entries.iter().map(|e| (e.name.as_str(), e)).collect();
+
+    let mut in_degree: HashMap<&str, usize> = HashMap::new();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for entry in entries {
+        in_degree.entry(entry.name.as_str()).or_insert(0);
+        for dep in &entry.depends_on {
+            if !by_name.contains_key(dep.as_str()) {
+                return Err(format!(
+                    "Script '{}' depends on unknown script '{}'",
+                    entry.name, dep
+                ));
+            }
+            *in_degree.entry(entry.name.as_str()).or_insert(0) += 1;
+            dependents
+                .entry(dep.as_str())
+                .or_default()
+                .push(entry.name.as_str());
+        }
+    }
+
+    // 保持注册顺序，使结果稳定
+    let mut queue: VecDeque<&str> = entries
+        .iter()
+        .map(|e| e.name.as_str())
+        .filter(|n| in_degree[n] == 0)
+        .collect();
+    let mut ordered = Vec::with_capacity(entries.len());
+
+    while let Some(name) = queue.pop_front() {
+        ordered.push(by_name[name].clone());
+        if let Some(children) = dependents.get(name) {
+            for child in children {
+                let degree = in_degree.get_mut(child).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(*child);
+                }
+            }
+        }
+    }
+
+    if ordered.len() != entries.len() {
+        let cyclic: Vec<&str> = entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .filter(|n| in_degree[n] > 0)
+            .collect();
+        return Err(format!(
+            "Dependency cycle detected between scripts: {}",
+            cyclic.join(", ")
+        ));
+    }
+
+    Ok(ordered)
+}
+
+// 启动单个脚本并按其就绪检查等待
+async fn start_and_wait(app: &AppHandle, entry: &ScriptEntry) -> Result<(), String> {
+    match &entry.ready {
+        Some(ReadinessCheck::Output {
+            pattern,
+            timeout_ms,
+        }) => {
+            let pattern =
+                Regex::new(pattern).map_err(|e| format!("Invalid ready pattern: {}", e))?;
+            let (tx, rx) = oneshot::channel();
+            let hooks = SpawnHooks {
+                ready: Some(ReadySignal { pattern, tx }),
+                ..Default::default()
+            };
+            script_manager::spawn_script(app, &entry.path, hooks).await?;
+            match tokio::time::timeout(Duration::from_millis(*timeout_ms), rx).await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(_)) => Err("Script exited before becoming ready".into()),
+                Err(_) => Err(format!("Timed out after {}ms waiting for readiness", timeout_ms)),
+            }
+        }
+        Some(ReadinessCheck::Port { port, timeout_ms }) => {
+            script_manager::spawn_script(app, &entry.path, SpawnHooks::default()).await?;
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(*timeout_ms);
+            loop {
+                if tokio::net::TcpStream::connect(("127.0.0.1", *port)).await.is_ok() {
+                    return Ok(());
+                }
+                if !script_manager::is_script_running(app, &entry.path).await {
+                    return Err("Script exited before becoming ready".into());
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(format!(
+                        "Timed out after {}ms waiting for port {}",
+                        timeout_ms, port
+                    ));
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+        Some(ReadinessCheck::Delay { ms }) => {
+            script_manager::spawn_script(app, &entry.path, SpawnHooks::default()).await?;
+            tokio::time::sleep(Duration::from_millis(*ms)).await;
+            if script_manager::is_script_running(app, &entry.path).await {
+                Ok(())
+            } else {
+                Err("Script exited before becoming ready".into())
+            }
+        }
+        None => {
+            script_manager::spawn_script(app, &entry.path, SpawnHooks::default()).await?;
+            Ok(())
+        }
+    }
+}
+
+// --- Tauri Commands ---
+
+#[tauri::command]
+pub fn list_registered_scripts(app: AppHandle) -> Result<Vec<ScriptEntry>, String> {
+    read_registry(&app)
+}
+
+// 注册或更新脚本 (按 name 覆盖)
+#[tauri::command]
+pub fn register_script(app: AppHandle, entry: ScriptEntry) -> Result<(), String> {
+    if entry.name.is_empty() {
+        return Err("Script name must not be empty".into());
+    }
+    if entry.depends_on.iter().any(|d| d == &entry.name) {
+        return Err(format!("Script '{}' cannot depend on itself", entry.name));
+    }
+
+    let mut entries = read_registry(&app)?;
+    match entries.iter_mut().find(|e| e.name == entry.name) {
+        Some(existing) => *existing = entry,
+        None => entries.push(entry),
+    }
+
+    // 保存前检查依赖关系，避免写入循环依赖
+    topo_sort(&entries)?;
+    write_registry(&app, &entries)
+}
+
+// 仍被其他脚本依赖、或被定时任务引用的脚本不能移除
+#[tauri::command]
+pub async fn unregister_script(app: AppHandle, name: String) -> Result<(), String> {
+    let schedules = script_scheduler::schedules_using_script(&app, &name).await;
+    if !schedules.is_empty() {
+        return Err(format!(
+            "Script '{}' is used by schedules: {}",
+            name,
+            schedules.join(", ")
+        ));
+    }
+
+    let mut entries = read_registry(&app)?;
+    let dependents: Vec<&str> = entries
+        .iter()
+        .filter(|e| e.depends_on.contains(&name))
+        .map(|e| e.name.as_str())
+        .collect();
+    if !dependents.is_empty() {
+        return Err(format!(
+            "Script '{}' is required by: {}",
+            name,
+            dependents.join(", ")
+        ));
+    }
+    entries.retain(|e| e.name != name);
+    write_registry(&app, &entries)
+}
+
+// 按拓扑顺序启动所有已注册的脚本，每个脚本在其依赖就绪后才会启动
+// 依赖启动失败时，依赖它的脚本会被跳过并报告原因
+#[tauri::command]
+pub async fn start_all_scripts(app: AppHandle) -> Result<Vec<ScriptStartReport>, String> {
+    let entries = read_registry(&app)?;
+    let ordered = topo_sort(&entries)?;
+
+    let mut failed: HashMap<String, String> = HashMap::new();
+    let mut reports = Vec::with_capacity(ordered.len());
+
+    for entry in &ordered {
+        if let Some(dep) = entry.depends_on.iter().find(|d| failed.contains_key(*d)) {
+            let reason = format!("Dependency '{}' failed: {}", dep, failed[dep]);
+            failed.insert(entry.name.clone(), reason.clone());
+            reports.push(ScriptStartReport {
+                name: entry.name.clone(),
+                status: "skipped".into(),
+                error: Some(reason),
+            });
+            continue;
+        }
+
+        if script_manager::is_script_running(&app, &entry.path).await {
+            reports.push(ScriptStartReport {
+                name: entry.name.clone(),
+                status: "already_running".into(),
+                error: None,
+            });
+            continue;
+        }
+
+        match start_and_wait(&app, entry).await {
+            Ok(()) => reports.push(ScriptStartReport {
+                name: entry.name.clone(),
+                status: "started".into(),
+                error: None,
+            }),
+            Err(e) => {
+                eprintln!("[Script Registry] Failed to start '{}': {}", entry.name, e);
+                failed.insert(entry.name.clone(), e.clone());
+                reports.push(ScriptStartReport {
+                    name: entry.name.clone(),
+                    status: "failed".into(),
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    Ok(reports)
+}
+
+// 按拓扑逆序停止所有已注册且正在运行的脚本 (先停依赖方，再停被依赖方)
+#[tauri::command]
+pub async fn stop_all_scripts(
+    app: AppHandle,
+    state: tauri::State<'_, ScriptProcessState>,
+) -> Result<Vec<String>, String> {
+    let entries = read_registry(&app)?;
+    let ordered = topo_sort(&entries)?;
+
+    let mut stopped = Vec::new();
+    for entry in ordered.iter().rev() {
+        let canonical = match PathBuf::from(&entry.path).canonicalize() {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(_) => continue,
+        };
+        if script_manager::kill_script(&state, &canonical).await.is_ok() {
+            stopped.push(entry.name.clone());
+        }
+    }
+
+    Ok(stopped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, depends_on: &[&str]) -> ScriptEntry {
+        ScriptEntry {
+            name: name.into(),
+            path: format!("/scripts/{}.sh", name),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ready: None,
+            limits: None,
+            restricted: false,
+            protocol: false,
+        }
+    }
+
+    fn names(entries: &[ScriptEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn dependencies_start_first() {
+        let entries = vec![
+            entry("app", &["db", "cache"]),
+            entry("cache", &["db"]),
+            entry("db", &[]),
+        ];
+        assert_eq!(
+            names(&topo_sort(&entries).unwrap()),
+            vec!["db", "cache", "app"]
+        );
+    }
+
+    #[test]
+    fn independent_scripts_keep_registration_order() {
+        let entries = vec![
+            entry("c", &[]),
+            entry("a", &[]),
+            entry("worker", &["a"]),
+            entry("b", &[]),
+        ];
+        assert_eq!(
+            names(&topo_sort(&entries).unwrap()),
+            vec!["c", "a", "b", "worker"]
+        );
+        assert!(topo_sort(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_dependency_is_an_error() {
+        let err = topo_sort(&[entry("app", &["db"])]).unwrap_err();
+        assert_eq!(err, "Script 'app' depends on unknown script 'db'");
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let err = topo_sort(&[entry("a", &["a"])]).unwrap_err();
+        assert_eq!(err, "Dependency cycle detected between scripts: a");
+
+        let entries = vec![
+            entry("free", &[]),
+            entry("a", &["c"]),
+            entry("b", &["a"]),
+            entry("c", &["b"]),
+        ];
+        let err = topo_sort(&entries).unwrap_err();
+        assert_eq!(err, "Dependency cycle detected between scripts: a, b, c");
+    }
+}
@@ -0,0 +1,339 @@
+// src-tauri/src/script_sandbox.rs
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// 脚本的资源限制，所有字段均为可选
+// - cpu_time_secs / memory_mb / max_open_files 通过 rlimit (ulimit) 设置
+// - memory_mb 在 use_cgroup 为 true 时改用 cgroup v2 (systemd-run --scope -p MemoryMax)
+// - nice 只允许降低优先级 (0..=19)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLimits {
+    pub cpu_time_secs: Option<u64>,
+    pub memory_mb: Option<u64>,
+    pub max_open_files: Option<u64>,
+    pub nice: Option<i32>,
+    #[serde(default)]
+    pub use_cgroup: bool,
+}
+
+impl ResourceLimits {
+    fn is_empty(&self) -> bool {
+        self.cpu_time_secs.is_none()
+            && self.memory_mb.is_none()
+            && self.max_open_files.is_none()
+            && self.nice.is_none()
+    }
+}
+
+// 在 PATH 中查找可执行文件
+pub(crate) fn find_in_path(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
+// 生成在 sh 中设置 rlimit 和 nice 后 exec 脚本的命令行
+// 脚本路径通过 $0、参数通过 $@ 传入，避免路径中的空格或特殊字符被 shell 解析
+fn build_shell_prelude(limits: &ResourceLimits) -> Result<String, String> {
+    let mut parts: Vec<String> = Vec::new();
+
+    if let Some(secs) = limits.cpu_time_secs {
+        parts.push(format!("ulimit -t {}", secs));
+    }
+    if let Some(mb) = limits.memory_mb {
+        if !limits.use_cgroup {
+            parts.push(format!("ulimit -v {}", mb * 1024));
+        }
+    }
+    if let Some(n) = limits.max_open_files {
+        parts.push(format!("ulimit -n {}", n));
+    }
+
+    let exec = match limits.nice {
+        Some(n) if !(0..=19).contains(&n) => {
+            return Err(format!("Invalid nice level {}: must be between 0 and 19", n));
+        }
+        Some(n) => format!("exec nice -n {} \"$0\" \"$@\"", n),
+        None => "exec \"$0\" \"$@\"".to_string(),
+    };
+    parts.push(exec);
+
+    Ok(parts.join(" && "))
+}
+
+// 构建受限模式 (bubblewrap) 的参数：
+// 整个文件系统只读挂载，仅 scratch_dir 可写，且不共享网络命名空间
+fn bwrap_args(scratch_dir: &Path) -> Vec<String> {
+    let scratch = scratch_dir.to_string_lossy().to_string();
+    vec![
+        "--ro-bind".into(),
+        "/".into(),
+        "/".into(),
+        "--dev".into(),
+        "/dev".into(),
+        "--proc".into(),
+        "/proc".into(),
+        "--tmpfs".into(),
+        "/tmp".into(),
+        "--bind".into(),
+        scratch.clone(),
+        scratch.clone(),
+        "--chdir".into(),
+        scratch.clone(),
+        "--setenv".into(),
+        "HOME".into(),
+        scratch,
+        "--unshare-all".into(),
+        "--die-with-parent".into(),
+        "--new-session".into(),
+        "--".into(),
+    ]
+}
+
+/// 根据资源限制和受限模式构建启动脚本的命令和参数
+/// 没有任何限制时保持原有的启动方式
+pub fn build_command(
+    script_path: &str,
+    limits: Option<&ResourceLimits>,
+    restricted: bool,
+    scratch_dir: &Path,
+) -> Result<(String, Vec<String>), String> {
+    let plain = if cfg!(windows) {
+        ("cmd".into(), vec!["/C".into(), script_path.into()])
+    } else {
+        ("sh".into(), vec!["-c".into(), script_path.into()])
+    };
+    wrap_command(plain, vec![script_path.into()], limits, restricted, scratch_dir)
+}
+
+/// 与 build_command 相同，但启动的是带参数的可执行文件 (例如 MCP 服务器)
+pub fn build_program_command(
+    program: &str,
+    args: &[String],
+    limits: Option<&ResourceLimits>,
+    restricted: bool,
+    scratch_dir: &Path,
+) -> Result<(String, Vec<String>), String> {
+    let mut target = vec![program.to_string()];
+    target.extend(args.iter().cloned());
+    wrap_command(
+        (program.to_string(), args.to_vec()),
+        target,
+        limits,
+        restricted,
+        scratch_dir,
+    )
+}
+
+// 没有限制时使用 plain 原样启动；否则由 sh 设置限制后 exec target (程序及其参数)
+fn wrap_command(
+    plain: (String, Vec<String>),
+    target: Vec<String>,
+    limits: Option<&ResourceLimits>,
+    restricted: bool,
+    scratch_dir: &Path,
+) -> Result<(String, Vec<String>), String> {
+    let limits = limits.filter(|l| !l.is_empty());
+
+    if limits.is_none() && !restricted {
+        return Ok(plain);
+    }
+
+    if cfg!(windows) {
+        return Err("Resource limits and restricted mode are not supported on Windows".into());
+    }
+
+    let needs_linux = restricted || limits.map(|l| l.use_cgroup).unwrap_or(false);
+    if needs_linux && !cfg!(target_os = "linux") {
+        return Err("Restricted mode and cgroup limits are only supported on Linux".into());
+    }
+
+    let prelude = build_shell_prelude(limits.unwrap_or(&ResourceLimits::default()))?;
+    let mut argv: Vec<String> = vec!["sh".into(), "-c".into(), prelude];
+    argv.extend(target);
+
+    if restricted {
+        let bwrap = find_in_path("bwrap").ok_or_else(|| {
+            "Restricted mode requires bubblewrap (bwrap) to be installed".to_string()
+        })?;
+        std::fs::create_dir_all(scratch_dir).map_err(|e| e.to_string())?;
+        let mut wrapped = vec![bwrap.to_string_lossy().to_string()];
+        wrapped.extend(bwrap_args(scratch_dir));
+        wrapped.extend(argv);
+        argv = wrapped;
+    }
+
+    if let Some(mb) = limits.filter(|l| l.use_cgroup).and_then(|l| l.memory_mb) {
+        let systemd_run = find_in_path("systemd-run")
+            .ok_or_else(|| "cgroup memory limits require systemd-run".to_string())?;
+        let mut wrapped = vec![
+            systemd_run.to_string_lossy().to_string(),
+            "--user".into(),
+            "--scope".into(),
+            "--quiet".into(),
+            "-p".into(),
+            format!("MemoryMax={}M", mb),
+            "-p".into(),
+            "MemorySwapMax=0".into(),
+            "--".into(),
+        ];
+        wrapped.extend(argv);
+        argv = wrapped;
+    }
+
+    let cmd = argv.remove(0);
+    Ok((cmd, argv))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn limits(cpu: Option<u64>, nice: Option<i32>) -> ResourceLimits {
+        ResourceLimits {
+            cpu_time_secs: cpu,
+            nice,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scripts_without_limits_start_unchanged() {
+        let scratch = Path::new("/tmp/scratch");
+        let expected = (
+            "sh".to_string(),
+            vec!["-c".to_string(), "/scripts/a b.sh".into()],
+        );
+        assert_eq!(
+            build_command("/scripts/a b.sh", None, false, scratch).unwrap(),
+            expected
+        );
+        // 所有字段都为空的限制等同于没有限制
+        assert_eq!(
+            build_command(
+                "/scripts/a b.sh",
+                Some(&ResourceLimits::default()),
+                false,
+                scratch
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn script_limits_are_applied_before_exec() {
+        let scratch = Path::new("/tmp/scratch");
+        let all = ResourceLimits {
+            cpu_time_secs: Some(5),
+            memory_mb: Some(64),
+            max_open_files: Some(32),
+            nice: Some(10),
+            use_cgroup: false,
+        };
+        let (cmd, args) = build_command("/scripts/a b.sh", Some(&all), false, scratch).unwrap();
+        assert_eq!(cmd, "sh");
+        assert_eq!(
+            args,
+            vec![
+                "-c".to_string(),
+                "ulimit -t 5 && ulimit -v 65536 && ulimit -n 32 && exec nice -n 10 \"$0\" \"$@\""
+                    .into(),
+                "/scripts/a b.sh".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn cgroup_memory_limit_replaces_ulimit() {
+        let limits = ResourceLimits {
+            memory_mb: Some(64),
+            use_cgroup: true,
+            ..Default::default()
+        };
+        assert_eq!(build_shell_prelude(&limits).unwrap(), "exec \"$0\" \"$@\"");
+    }
+
+    #[test]
+    fn rejects_nice_levels_that_raise_priority() {
+        let scratch = Path::new("/tmp/scratch");
+        assert!(build_command(
+            "/scripts/a.sh",
+            Some(&limits(None, Some(-5))),
+            false,
+            scratch
+        )
+        .is_err());
+        assert!(build_command(
+            "/scripts/a.sh",
+            Some(&limits(None, Some(20))),
+            false,
+            scratch
+        )
+        .is_err());
+        assert!(build_command(
+            "/scripts/a.sh",
+            Some(&limits(None, Some(19))),
+            false,
+            scratch
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn restricted_mode_only_allows_writing_to_scratch_dir() {
+        let args = bwrap_args(Path::new("/data/scratch/job"));
+        assert_eq!(&args[..3], ["--ro-bind", "/", "/"]);
+        let bind = args.iter().position(|a| a == "--bind").unwrap();
+        assert_eq!(
+            &args[bind + 1..bind + 3],
+            ["/data/scratch/job", "/data/scratch/job"]
+        );
+        assert_eq!(args.iter().filter(|a| a.as_str() == "--bind").count(), 1);
+        assert!(args.contains(&"--unshare-all".to_string()));
+        assert_eq!(args.last().unwrap(), "--");
+    }
+
+    #[test]
+    fn programs_without_limits_start_unchanged() {
+        let program_args = vec!["-y".to_string(), "server".into()];
+        let (cmd, args) = build_program_command(
+            "/usr/bin/npx",
+            &program_args,
+            Some(&ResourceLimits::default()),
+            false,
+            Path::new("/tmp/scratch"),
+        )
+        .unwrap();
+        assert_eq!(cmd, "/usr/bin/npx");
+        assert_eq!(args, program_args);
+    }
+
+    #[test]
+    fn limits_are_applied_before_exec_with_arguments() {
+        let scratch = Path::new("/tmp/scratch");
+        let program_args = vec!["--port".to_string(), "1 2".into()];
+        let (cmd, args) = build_program_command(
+            "/usr/bin/server",
+            &program_args,
+            Some(&limits(Some(5), Some(10))),
+            false,
+            scratch,
+        )
+        .unwrap();
+        assert_eq!(cmd, "sh");
+        assert_eq!(
+            args,
+            vec![
+                "-c".to_string(),
+                "ulimit -t 5 && exec nice -n 10 \"$0\" \"$@\"".into(),
+                "/usr/bin/server".into(),
+                "--port".into(),
+                "1 2".into(),
+            ]
+        );
+    }
+}
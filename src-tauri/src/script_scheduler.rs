@@ -0,0 +1,615 @@
+// src-tauri/src/script_scheduler.rs
+
+use crate::atomic_file;
+use crate::script_manager::{self, ScriptExit, SpawnHooks};
+use crate::script_registry;
+use chrono::{DateTime, Local, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{oneshot, Mutex};
+
+// 运行记录最多保留的条数
+const MAX_HISTORY: usize = 500;
+const HISTORY_FILE: &str = "schedule_history.json";
+// 调度循环的检查间隔
+const TICK: Duration = Duration::from_secs(1);
+
+// 触发方式：cron 表达式 (本地时间，支持 5 段或 6 段带秒的写法) 或固定间隔
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Trigger {
+    Cron { expr: String },
+    Interval { seconds: u64 },
+}
+
+// 一个定时任务，script 为已注册脚本的 name
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleEntry {
+    pub id: String,
+    pub script: String,
+    pub trigger: Trigger,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// 返回给前端的任务信息，附带下一次运行时间
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleInfo {
+    #[serde(flatten)]
+    pub entry: ScheduleEntry,
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    pub next_run: Option<DateTime<Utc>>,
+    pub running: bool,
+}
+
+// 一次运行的记录
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    pub schedule_id: String,
+    pub script: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    pub ended_at: Option<DateTime<Utc>>,
+    pub status: String, // "success" | "failed" | "skipped" | "error"
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub output: String,
+    pub output_truncated: bool,
+    pub error: Option<String>,
+}
+
+// 调度器状态
+pub struct SchedulerState {
+    pub schedules: Mutex<Vec<ScheduleEntry>>,
+    // 正在运行的任务 id，用于防止同一任务重叠运行
+    pub running: Mutex<HashSet<String>>,
+    // 串行化运行记录的读改写，多个任务同时结束时不会丢失记录
+    history: Mutex<()>,
+}
+
+impl SchedulerState {
+    pub fn new() -> Self {
+        Self {
+            schedules: Mutex::new(Vec::new()),
+            running: Mutex::new(HashSet::new()),
+            history: Mutex::new(()),
+        }
+    }
+
+    // 标记任务为运行中；已经在运行时返回 false
+    async fn try_claim(&self, id: &str) -> bool {
+        self.running.lock().await.insert(id.to_string())
+    }
+
+    async fn release(&self, id: &str) {
+        self.running.lock().await.remove(id);
+    }
+}
+
+// --- 辅助函数 ---
+
+fn get_data_file(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(name))
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(
+    app: &AppHandle,
+    name: &str,
+) -> Result<T, String> {
+    read_json_file(&get_data_file(app, name)?)
+}
+
+fn read_json_file<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid {}: {}", name, e))
+}
+
+fn write_json<T: Serialize + ?Sized>(app: &AppHandle, name: &str, value: &T) -> Result<(), String> {
+    write_json_file(&get_data_file(app, name)?, value)
+}
+
+// 原子写入，读取方不会读到写了一半的文件
+fn write_json_file<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    atomic_file::write_atomic(path, contents.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn save_schedules(app: &AppHandle, schedules: &[ScheduleEntry]) -> Result<(), String> {
+    write_json(app, "schedules.json", schedules)
+}
+
+/// 引用了该脚本的定时任务 id
+pub(crate) async fn schedules_using_script(app: &AppHandle, script: &str) -> Vec<String> {
+    app.state::<SchedulerState>()
+        .schedules
+        .lock()
+        .await
+        .iter()
+        .filter(|e| e.script == script)
+        .map(|e| e.id.clone())
+        .collect()
+}
+
+// 调用方需持有 SchedulerState::history 锁
+fn append_history_file(path: &Path, record: RunRecord) -> Result<(), String> {
+    let mut history: Vec<RunRecord> = read_json_file(path)?;
+    history.push(record);
+    if history.len() > MAX_HISTORY {
+        let excess = history.len() - MAX_HISTORY;
+        history.drain(..excess);
+    }
+    write_json_file(path, &history)
+}
+
+async fn append_history(app: &AppHandle, record: RunRecord) -> Result<(), String> {
+    let _guard = app.state::<SchedulerState>().history.lock().await;
+    append_history_file(&get_data_file(app, HISTORY_FILE)?, record)
+}
+
+// 解析 cron 表达式，5 段写法会自动补上秒字段
+fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let expr = expr.trim();
+    let full = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    Schedule::from_str(&full).map_err(|e| format!("Invalid cron expression '{}': {}", expr, e))
+}
+
+// 计算下一次运行时间
+// 从上次运行 (或创建) 时间开始推算，这样应用关闭期间错过的运行会在启动后补跑一次
+fn next_run(entry: &ScheduleEntry, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if !entry.enabled {
+        return None;
+    }
+    let base = entry.last_run.or(entry.created_at).unwrap_or(now);
+    match &entry.trigger {
+        Trigger::Interval { seconds } => Some(base + chrono::Duration::seconds(*seconds as i64)),
+        Trigger::Cron { expr } => {
+            let schedule = parse_cron(expr).ok()?;
+            schedule
+                .after(&base.with_timezone(&Local))
+                .next()
+                .map(|t| t.with_timezone(&Utc))
+        }
+    }
+}
+
+fn validate_entry(entry: &ScheduleEntry) -> Result<(), String> {
+    if entry.id.is_empty() {
+        return Err("Schedule id must not be empty".into());
+    }
+    match &entry.trigger {
+        Trigger::Cron { expr } => parse_cron(expr).map(|_| ()),
+        Trigger::Interval { seconds } if *seconds == 0 => {
+            Err("Interval must be at least 1 second".into())
+        }
+        Trigger::Interval { .. } => Ok(()),
+    }
+}
+
+// 记录调度触发的运行时间，返回是否有修改；手动运行不改变 last_run，不影响下一次调度
+fn mark_started(schedules: &mut [ScheduleEntry], id: &str, started_at: DateTime<Utc>, manual: bool) -> bool {
+    if manual {
+        return false;
+    }
+    match schedules.iter_mut().find(|e| e.id == id) {
+        Some(e) => {
+            e.last_run = Some(started_at);
+            true
+        }
+        None => false,
+    }
+}
+
+// 执行一次定时任务，等待脚本结束并记录结果；manual 表示由 run_schedule_now 触发
+async fn run_schedule(app: AppHandle, entry: ScheduleEntry, manual: bool) {
+    let state = app.state::<SchedulerState>();
+    let started_at = Utc::now();
+
+    // 更新 last_run，保证即使运行失败也不会在下一个 tick 立即重试
+    {
+        let mut schedules = state.schedules.lock().await;
+        if mark_started(&mut schedules, &entry.id, started_at, manual) {
+            if let Err(e) = save_schedules(&app, &schedules) {
+                eprintln!("[Scheduler] Failed to save schedules: {}", e);
+            }
+        }
+    }
+
+    let mut record = RunRecord {
+        schedule_id: entry.id.clone(),
+        script: entry.script.clone(),
+        started_at,
+        ended_at: None,
+        status: "error".into(),
+        exit_code: None,
+        signal: None,
+        output: String::new(),
+        output_truncated: false,
+        error: None,
+    };
+
+    let script = script_registry::read_registry(&app)
+        .ok()
+        .and_then(|entries| entries.into_iter().find(|e| e.name == entry.script));
+
+    match script {
+        None => {
+            record.error = Some(format!("Script '{}' is not registered", entry.script));
+        }
+        Some(s) => {
+            if script_manager::is_script_running(&app, &s.path).await {
+                // 脚本已经在运行 (例如用户手动启动)，跳过本次运行
+                record.status = "skipped".into();
+                record.error = Some("Script is already running".into());
+            } else {
+                let (tx, rx) = oneshot::channel::<ScriptExit>();
+                let hooks = SpawnHooks {
+                    exit: Some(tx),
+                    ..Default::default()
+                };
+                match script_manager::spawn_script(&app, &s.path, hooks).await {
+                    Err(e) => record.error = Some(e),
+                    Ok(_) => match rx.await {
+                        Ok(exit) => {
+                            record.status = if exit.code == Some(0) {
+                                "success".into()
+                            } else {
+                                "failed".into()
+                            };
+                            record.exit_code = exit.code;
+                            record.signal = exit.signal;
+                            record.output = exit.output;
+                            record.output_truncated = exit.output_truncated;
+                        }
+                        Err(_) => record.error = Some("Lost track of script process".into()),
+                    },
+                }
+            }
+        }
+    }
+
+    record.ended_at = Some(Utc::now());
+    let _ = app.emit("schedule-run-finished", &record);
+    if let Err(e) = append_history(&app, record).await {
+        eprintln!("[Scheduler] Failed to record run history: {}", e);
+    }
+
+    state.release(&entry.id).await;
+}
+
+// 如果任务没有在运行，则标记为运行中并在后台执行
+async fn try_start(app: &AppHandle, entry: ScheduleEntry, manual: bool) -> bool {
+    if !app.state::<SchedulerState>().try_claim(&entry.id).await {
+        return false;
+    }
+    tauri::async_runtime::spawn(run_schedule(app.clone(), entry, manual));
+    true
+}
+
+/// 加载已保存的定时任务并启动调度循环
+pub fn init_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<SchedulerState>();
+        match read_json::<Vec<ScheduleEntry>>(&app, "schedules.json") {
+            Ok(mut entries) => {
+                // 手动编辑过的文件可能缺少 created_at，以当前时间作为推算起点
+                let now = Utc::now();
+                for e in entries.iter_mut().filter(|e| e.created_at.is_none()) {
+                    e.created_at = Some(now);
+                }
+                *state.schedules.lock().await = entries;
+            }
+            Err(e) => eprintln!("[Scheduler] Failed to load schedules: {}", e),
+        }
+
+        loop {
+            tokio::time::sleep(TICK).await;
+            let now = Utc::now();
+            let due: Vec<ScheduleEntry> = state
+                .schedules
+                .lock()
+                .await
+                .iter()
+                .filter(|e| next_run(e, now).map(|t| t <= now).unwrap_or(false))
+                .cloned()
+                .collect();
+
+            for entry in due {
+                // 上一次运行还没结束时跳过，等待下一个触发时间
+                if !try_start(&app, entry.clone(), false).await {
+                    continue;
+                }
+                println!("[Scheduler] Running schedule '{}' ({})", entry.id, entry.script);
+            }
+        }
+    });
+}
+
+// --- Tauri Commands ---
+
+#[tauri::command]
+pub async fn list_schedules(state: State<'_, SchedulerState>) -> Result<Vec<ScheduleInfo>, String> {
+    let now = Utc::now();
+    let running = state.running.lock().await.clone();
+    let schedules = state.schedules.lock().await;
+    Ok(schedules
+        .iter()
+        .map(|e| ScheduleInfo {
+            entry: e.clone(),
+            next_run: next_run(e, now),
+            running: running.contains(&e.id),
+        })
+        .collect())
+}
+
+// 新建或更新定时任务 (按 id 覆盖，保留原有的 created_at 和 last_run)
+#[tauri::command]
+pub async fn upsert_schedule(
+    app: AppHandle,
+    state: State<'_, SchedulerState>,
+    mut entry: ScheduleEntry,
+) -> Result<(), String> {
+    validate_entry(&entry)?;
+    let mut schedules = state.schedules.lock().await;
+    match schedules.iter_mut().find(|e| e.id == entry.id) {
+        Some(existing) => {
+            entry.created_at = existing.created_at;
+            entry.last_run = existing.last_run;
+            *existing = entry;
+        }
+        None => {
+            entry.created_at = Some(Utc::now());
+            entry.last_run = None;
+            schedules.push(entry);
+        }
+    }
+    save_schedules(&app, &schedules)
+}
+
+#[tauri::command]
+pub async fn delete_schedule(
+    app: AppHandle,
+    state: State<'_, SchedulerState>,
+    id: String,
+) -> Result<(), String> {
+    let mut schedules = state.schedules.lock().await;
+    schedules.retain(|e| e.id != id);
+    save_schedules(&app, &schedules)
+}
+
+// 立即运行一次，记录运行结果但不改变 last_run，不影响原有的调度
+#[tauri::command]
+pub async fn run_schedule_now(
+    app: AppHandle,
+    state: State<'_, SchedulerState>,
+    id: String,
+) -> Result<(), String> {
+    let entry = state
+        .schedules
+        .lock()
+        .await
+        .iter()
+        .find(|e| e.id == id)
+        .cloned()
+        .ok_or_else(|| format!("Schedule '{}' not found", id))?;
+
+    if try_start(&app, entry, true).await {
+        Ok(())
+    } else {
+        Err("Schedule is already running".into())
+    }
+}
+
+// 查询运行记录 (最新的在前)，可按任务过滤
+#[tauri::command]
+pub fn get_schedule_history(
+    app: AppHandle,
+    schedule_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<RunRecord>, String> {
+    let history: Vec<RunRecord> = read_json(&app, HISTORY_FILE)?;
+    Ok(history
+        .into_iter()
+        .rev()
+        .filter(|r| schedule_id.as_ref().map(|id| &r.schedule_id == id).unwrap_or(true))
+        .take(limit.unwrap_or(100))
+        .collect())
+}
+
+#[tauri::command]
+pub async fn clear_schedule_history(app: AppHandle, state: State<'_, SchedulerState>) -> Result<(), String> {
+    let _guard = state.history.lock().await;
+    write_json::<[RunRecord]>(&app, HISTORY_FILE, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, TimeZone};
+
+    fn entry(trigger: Trigger) -> ScheduleEntry {
+        ScheduleEntry {
+            id: "job".into(),
+            script: "script".into(),
+            trigger,
+            enabled: true,
+            created_at: None,
+            last_run: None,
+        }
+    }
+
+    #[test]
+    fn interval_fires_after_last_run_or_creation() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut e = entry(Trigger::Interval { seconds: 90 });
+        assert_eq!(next_run(&e, now), Some(now + ChronoDuration::seconds(90)));
+
+        e.created_at = Some(now - ChronoDuration::seconds(30));
+        assert_eq!(next_run(&e, now), Some(now + ChronoDuration::seconds(60)));
+
+        e.last_run = Some(now);
+        assert_eq!(next_run(&e, now), Some(now + ChronoDuration::seconds(90)));
+
+        e.enabled = false;
+        assert_eq!(next_run(&e, now), None);
+    }
+
+    #[test]
+    fn cron_accepts_five_and_six_fields() {
+        let base = Local
+            .with_ymd_and_hms(2024, 5, 1, 12, 7, 30)
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut e = entry(Trigger::Cron {
+            expr: "*/15 * * * *".into(),
+        });
+        e.created_at = Some(base);
+        let expected = Local
+            .with_ymd_and_hms(2024, 5, 1, 12, 15, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(next_run(&e, base), Some(expected));
+
+        e.trigger = Trigger::Cron {
+            expr: "45 * * * * *".into(),
+        };
+        let expected = Local
+            .with_ymd_and_hms(2024, 5, 1, 12, 7, 45)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(next_run(&e, base), Some(expected));
+
+        e.trigger = Trigger::Cron {
+            expr: "not a cron".into(),
+        };
+        assert_eq!(next_run(&e, base), None);
+        assert!(validate_entry(&e).is_err());
+    }
+
+    #[test]
+    fn missed_runs_are_caught_up_once_after_restart() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        // 应用关闭了一小时，期间错过了 60 次运行
+        let mut e = entry(Trigger::Interval { seconds: 60 });
+        e.created_at = Some(now - ChronoDuration::hours(2));
+        e.last_run = Some(now - ChronoDuration::hours(1));
+        assert!(next_run(&e, now).unwrap() <= now);
+
+        // 补跑一次后，下一次运行回到正常的间隔，不会把错过的次数逐一补上
+        e.last_run = Some(now);
+        assert!(next_run(&e, now).unwrap() > now);
+
+        let mut e = entry(Trigger::Cron {
+            expr: "0 * * * *".into(),
+        });
+        e.last_run = Some(now - ChronoDuration::days(1));
+        assert!(next_run(&e, now).unwrap() <= now);
+        e.last_run = Some(now);
+        assert!(next_run(&e, now).unwrap() > now);
+    }
+
+    #[tokio::test]
+    async fn running_schedule_cannot_overlap() {
+        let state = SchedulerState::new();
+        assert!(state.try_claim("job").await);
+        assert!(!state.try_claim("job").await);
+        assert!(state.try_claim("other").await);
+
+        state.release("job").await;
+        assert!(state.try_claim("job").await);
+    }
+
+    #[test]
+    fn rejects_empty_id_and_zero_interval() {
+        let mut e = entry(Trigger::Interval { seconds: 0 });
+        assert!(validate_entry(&e).is_err());
+        e.trigger = Trigger::Interval { seconds: 1 };
+        assert!(validate_entry(&e).is_ok());
+        e.id.clear();
+        assert!(validate_entry(&e).is_err());
+    }
+
+    #[test]
+    fn manual_runs_do_not_move_the_schedule() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut e = entry(Trigger::Interval { seconds: 60 });
+        e.created_at = Some(now - ChronoDuration::seconds(30));
+        let mut schedules = vec![e];
+        let before = next_run(&schedules[0], now);
+
+        assert!(!mark_started(&mut schedules, "job", now, true));
+        assert_eq!(schedules[0].last_run, None);
+        assert_eq!(next_run(&schedules[0], now), before);
+
+        assert!(mark_started(&mut schedules, "job", now, false));
+        assert_eq!(schedules[0].last_run, Some(now));
+        assert!(!mark_started(&mut schedules, "missing", now, false));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_history_appends_keep_every_record() {
+        let dir = std::env::temp_dir().join(format!("schedule-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(HISTORY_FILE);
+        let state = std::sync::Arc::new(SchedulerState::new());
+
+        let tasks: Vec<_> = (0..16)
+            .map(|i| {
+                let state = state.clone();
+                let path = path.clone();
+                tokio::spawn(async move {
+                    let _guard = state.history.lock().await;
+                    let record = RunRecord {
+                        schedule_id: format!("job-{}", i),
+                        script: "script".into(),
+                        started_at: Utc::now(),
+                        ended_at: None,
+                        status: "success".into(),
+                        exit_code: Some(0),
+                        signal: None,
+                        output: String::new(),
+                        output_truncated: false,
+                        error: None,
+                    };
+                    append_history_file(&path, record).unwrap();
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let history: Vec<RunRecord> = read_json_file(&path).unwrap();
+        let ids: HashSet<String> = history.into_iter().map(|r| r.schedule_id).collect();
+        assert_eq!(ids.len(), 16);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
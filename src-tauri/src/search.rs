@@ -1,51 +1,330 @@
+use crate::search_archive;
+use crate::search_index;
+use crate::search_json::{self, JsonMatch, JsonPath};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{WalkBuilder, WalkState};
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use std::path::Path;
-use walkdir::WalkDir;
-use regex::RegexBuilder;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use regex::{Regex, RegexBuilder};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[derive(Debug, Deserialize)]
 pub struct SearchConfig {
-    case_sensitive: bool,
-    whole_word: bool,
-    is_regex: bool,
-    target_dir: String, // 搜索的目标目录，通常是当前打开的工作区
+    pub(crate) case_sensitive: bool,
+    pub(crate) whole_word: bool,
+    pub(crate) is_regex: bool,
+    pub(crate) target_dir: String, // 搜索的目标目录，通常是当前打开的工作区
+    #[serde(default)]
+    pub(crate) max_results: Option<usize>, // 所有文件的匹配总数上限
+    #[serde(default)]
+    pub(crate) max_matches_per_file: Option<usize>,
+    #[serde(default)]
+    pub(crate) context_before: usize, // 每个匹配前附带的上下文行数
+    #[serde(default)]
+    pub(crate) context_after: usize, // 每个匹配后附带的上下文行数
+    #[serde(default)]
+    pub(crate) include: Vec<String>, // 只搜索匹配这些 glob 的文件，例如 "**/*.json"
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>, // 跳过匹配这些 glob 的文件或目录
+    #[serde(default)]
+    pub(crate) max_file_size: Option<u64>, // 跳过超过该大小 (字节) 的文件
+    #[serde(default = "default_include_hidden")]
+    pub(crate) include_hidden: bool,
+    #[serde(default)]
+    pub(crate) mode: SearchMode,
+    #[serde(default)]
+    pub(crate) fuzzy: bool, // 仅对 file_name / path 模式有效
+    #[serde(default)]
+    pub(crate) multiline: bool, // 在整个文件上匹配，允许模式跨越多行
+    #[serde(default)]
+    pub(crate) json_path: Option<String>, // 仅对 json 模式有效，例如 "entries[*].content"
+    #[serde(default)]
+    pub(crate) search_archives: bool, // 同时搜索目录中 .zip 压缩包内的文件；target_dir 本身是压缩包时总是搜索
 }
 
-#[derive(Debug, Serialize)]
+fn default_include_hidden() -> bool {
+    true
+}
+
+// content: 搜索文件内容；file_name / path: 只匹配文件名或相对路径
+// json: 解析 .json 文件，只匹配字符串值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    #[default]
+    Content,
+    FileName,
+    Path,
+    Json,
+}
+
+// 一行中的一处匹配，同时给出字节偏移和 UTF-16 偏移 (前端字符串按 UTF-16 计算)
+#[derive(Debug, Clone, Serialize)]
+pub struct SubMatch {
+    start: usize,
+    end: usize,
+    start_utf16: usize,
+    end_utf16: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextLine {
+    line_number: usize,
+    line: String,
+}
+
+// 匹配所在的行 (未去除空白，保证偏移正确)
+// 多行模式下 line 为 line_number 到 end_line_number 之间的原文 (包含换行)
+#[derive(Debug, Clone, Serialize)]
+pub struct LineMatch {
+    line_number: usize, // 从 1 开始
+    end_line_number: usize,
+    line: String,
+    submatches: Vec<SubMatch>,
+    before: Vec<ContextLine>,
+    after: Vec<ContextLine>,
+}
+
+// 文件名 / 路径模式下的匹配信息，ranges 为 UTF-16 偏移 [start, end)
+#[derive(Debug, Clone, Serialize)]
+pub struct NameMatch {
+    text: String,
+    score: i64,
+    ranges: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     path: String,
+    // 兼容旧格式： "行号: 内容" (去除了首尾空格)
     result: Vec<String>,
+    matches: Vec<LineMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_match: Option<NameMatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    json_matches: Vec<JsonMatch>,
 }
 
-fn is_binary_file(path: &Path) -> bool {
-    let mut file = match File::open(path) {
-        Ok(f) => f,
-        Err(_) => return true, // 无法打开视为“不可读/二进制”以跳过
-    };
-    let mut buffer = [0; 1024];
-    let n = match file.read(&mut buffer) {
-        Ok(n) => n,
-        Err(_) => return true,
-    };
-    // 简单启发式：如果前1024字节包含NULL字节，视为二进制
-    buffer[..n].contains(&0)
+impl SearchResult {
+    // 计入 max_results 的匹配数，文件名模式下每个文件算一个
+    fn len(&self) -> usize {
+        if self.name_match.is_some() {
+            1
+        } else {
+            self.matches.len() + self.json_matches.len()
+        }
+    }
+
+    fn truncate(&mut self, n: usize) {
+        self.result.truncate(n);
+        self.matches.truncate(n);
+        self.json_matches.truncate(n);
+        if n == 0 {
+            self.name_match = None;
+        }
+    }
+
+    // 按剩余的总数额度截断，返回是否真的丢弃了匹配
+    fn limit_to(&mut self, remaining: usize) -> bool {
+        let discarded = self.len() > remaining;
+        self.truncate(remaining);
+        discarded
+    }
 }
 
-#[tauri::command]
-pub async fn search_in_files(keyword: String, config: SearchConfig) -> Result<Vec<SearchResult>, String> {
-    if keyword.is_empty() {
-        return Ok(vec![]);
+// 无法读取或解码的文件 (二进制文件不算在内)
+#[derive(Debug, Clone, Serialize)]
+pub struct UnreadableFile {
+    path: Option<String>, // 遍历目录出错时可能没有具体路径
+    reason: String,
+}
+
+impl UnreadableFile {
+    pub(crate) fn new(path: Option<String>, reason: String) -> Self {
+        Self { path, reason }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    search_id: String,
+    results: Vec<SearchResult>,
+    truncated: bool, // 达到 max_results 或 max_matches_per_file 上限
+    cancelled: bool, // 被 cancel_search 或同一调用方的新搜索取消
+    unreadable: Vec<UnreadableFile>,
+}
+
+// 正在进行的搜索
+#[derive(Default)]
+pub struct SearchState {
+    // search_id -> 取消标记
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    // 调用方 -> 该调用方最近一次的 search_id，用于自动取消旧搜索
+    by_caller: Mutex<HashMap<String, String>>,
+}
+
+impl SearchState {
+    // 登记新的搜索，并取消同一调用方之前的搜索
+    fn begin(&self, search_id: &str, caller: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut active = self.active.lock().unwrap();
+        let previous = self
+            .by_caller
+            .lock()
+            .unwrap()
+            .insert(caller.to_string(), search_id.to_string());
+        if let Some(previous) = previous {
+            if let Some(old) = active.get(&previous) {
+                old.store(true, Ordering::SeqCst);
+            }
+        }
+        active.insert(search_id.to_string(), flag.clone());
+        flag
+    }
+
+    fn finish(&self, search_id: &str, caller: &str) {
+        self.active.lock().unwrap().remove(search_id);
+        let mut by_caller = self.by_caller.lock().unwrap();
+        if by_caller.get(caller).map(|id| id == search_id).unwrap_or(false) {
+            by_caller.remove(caller);
+        }
+    }
+}
+
+// 流式推送给前端的单个文件结果
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResultEvent<'a> {
+    search_id: &'a str,
+    result: &'a SearchResult,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchStartedEvent<'a> {
+    search_id: &'a str,
+    caller: &'a str,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchFinishedEvent<'a> {
+    search_id: &'a str,
+    file_count: usize,
+    truncated: bool,
+    cancelled: bool,
+    unreadable: &'a [UnreadableFile],
+}
+
+fn new_search_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
+// 判断编码和二进制内容时检查的开头字节数
+const SNIFF_LEN: usize = 4096;
+// 没有设置 max_file_size 时，单个文本文件读取的上限
+pub(crate) const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+// 没有 BOM 的 UTF-16 文本：NULL 字节集中在奇数位 (LE) 或偶数位 (BE)
+fn guess_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    let half = sample.len() / 2;
+    if half == 0 {
+        None
+    } else if even == 0 && odd * 2 >= half {
+        Some(UTF_16LE)
+    } else if odd == 0 && even * 2 >= half {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+// 没有 BOM、含有 NULL 字节且不像 UTF-16 的内容视为二进制
+fn is_binary_sample(sample: &[u8]) -> bool {
+    Encoding::for_bom(sample).is_none() && sample.contains(&0) && guess_utf16(sample).is_none()
+}
+
+/// 读取文本文件并识别编码：优先使用 BOM，其次按 UTF-8 解析，失败时由 chardetng 猜测 (如 GBK)
+/// 二进制文件返回 Ok(None)，读取或解码失败、超过 DEFAULT_MAX_FILE_SIZE 返回 Err
+pub(crate) fn read_text_file(path: &Path) -> Result<Option<String>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    read_text(file, DEFAULT_MAX_FILE_SIZE)
+}
+
+// 从文件或压缩包条目中读取文本，规则同 read_text_file，最多读取 max_size 字节
+pub(crate) fn read_text(reader: impl Read, max_size: u64) -> Result<Option<String>, String> {
+    match read_bytes(reader, max_size)? {
+        Some(bytes) => decode_text(&bytes),
+        None => Ok(None),
+    }
+}
+
+// 读取原始字节：开头是二进制内容时返回 None，超过 max_size 字节时返回错误
+pub(crate) fn read_bytes(mut reader: impl Read, max_size: u64) -> Result<Option<Vec<u8>>, String> {
+    // 先只读开头判断是否为二进制，避免把大的二进制文件整个读入内存
+    let mut bytes = Vec::new();
+    (&mut reader)
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if is_binary_sample(&bytes) {
+        return Ok(None);
+    }
+
+    // 多读一个字节，用于发现超过上限的内容 (包括读取过程中变大的文件、大小与头部声明不符的条目)
+    let rest = (max_size + 1).saturating_sub(bytes.len() as u64);
+    reader.take(rest).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    if bytes.len() as u64 > max_size {
+        return Err(format!("File is larger than {} bytes", max_size));
     }
+    Ok(Some(bytes))
+}
 
-    let mut results: Vec<SearchResult> = Vec::new();
+// 识别编码并解码，规则同 read_text_file
+pub(crate) fn decode_text(bytes: &[u8]) -> Result<Option<String>, String> {
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let (encoding, body) = if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        (encoding, &bytes[bom_len..])
+    } else if sample.contains(&0) {
+        match guess_utf16(sample) {
+            Some(encoding) => (encoding, bytes),
+            None => return Ok(None),
+        }
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        return Ok(Some(text.to_string()));
+    } else {
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        (detector.guess(None, false), bytes)
+    };
 
-    // 构建正则表达式
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    if had_errors {
+        return Err(format!("Invalid {} content", encoding.name()));
+    }
+    Ok(Some(text.into_owned()))
+}
+
+// 根据配置构建正则表达式
+pub(crate) fn build_regex(keyword: &str, config: &SearchConfig) -> Result<Regex, String> {
     let pattern = if config.is_regex {
-        keyword.clone()
+        keyword.to_string()
     } else {
-        regex::escape(&keyword)
+        regex::escape(keyword)
     };
 
     let final_pattern = if config.whole_word {
@@ -54,54 +333,878 @@ pub async fn search_in_files(keyword: String, config: SearchConfig) -> Result<Ve
         pattern
     };
 
-    let re = RegexBuilder::new(&final_pattern)
+    // 多行模式下 ^ / $ 匹配每一行的开头和结尾，. 仍不匹配换行
+    RegexBuilder::new(&final_pattern)
         .case_insensitive(!config.case_sensitive)
+        .multi_line(config.multiline)
+        .crlf(config.multiline)
         .build()
-        .map_err(|e| format!("Invalid regex: {}", e))?;
+        .map_err(|e| format!("Invalid regex: {}", e))
+}
+
+// include / exclude glob，相对于 target_dir (压缩包内则相对于压缩包根目录)
+pub(crate) fn build_overrides(config: &SearchConfig) -> Result<Override, String> {
+    let mut overrides = OverrideBuilder::new(&config.target_dir);
+    for glob in &config.include {
+        overrides
+            .add(glob)
+            .map_err(|e| format!("Invalid include glob '{}': {}", glob, e))?;
+    }
+    for glob in &config.exclude {
+        overrides
+            .add(&format!("!{}", glob))
+            .map_err(|e| format!("Invalid exclude glob '{}': {}", glob, e))?;
+    }
+    overrides.build().map_err(|e| e.to_string())
+}
+
+// 构建遍历器：遵守 .gitignore / .ignore，不要求目录是 git 仓库
+// data_dir 为应用数据目录，其中的密钥文件和内部目录 (索引、撤销记录等) 总是跳过
+pub(crate) fn build_walker(config: &SearchConfig, data_dir: &Path) -> Result<WalkBuilder, String> {
+    let overrides = build_overrides(config)?;
+
+    // 按规范化后的路径比较，target_dir 可能是符号链接或带有 ".."
+    let root = PathBuf::from(&config.target_dir);
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+    let data_dir = data_dir.canonicalize().unwrap_or_else(|_| data_dir.to_path_buf());
+    if search_index::is_excluded_dir(&data_dir, &canonical_root) {
+        return Err(format!("Cannot search in app internal files: {}", config.target_dir));
+    }
 
-    // 遍历文件
-    for entry in WalkDir::new(&config.target_dir)
+    let mut builder = WalkBuilder::new(&config.target_dir);
+    builder
         .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        if path.is_file() {
-            // 简单的忽略逻辑：跳过 .git, node_modules 等
-            let path_str = path.to_string_lossy();
-            if path_str.contains("node_modules") || path_str.contains(".git") || path_str.contains("target") {
-                continue;
+        .hidden(!config.include_hidden)
+        .git_ignore(true)
+        .git_global(false)
+        .git_exclude(true)
+        .ignore(true)
+        .parents(true)
+        .require_git(false)
+        .max_filesize(config.max_file_size)
+        .overrides(overrides)
+        .filter_entry(move |entry| {
+            // .git 目录本身不在 .gitignore 中，需要单独跳过
+            if entry.file_name() == ".git" {
+                return false;
+            }
+            let path = match entry.path().strip_prefix(&root) {
+                Ok(relative) => canonical_root.join(relative),
+                Err(_) => entry.path().to_path_buf(),
+            };
+            !search_index::is_excluded_dir(&data_dir, &path)
+        });
+    Ok(builder)
+}
+
+// 模糊匹配：pattern 的字符需按顺序出现在 candidate 中
+// 连续匹配、单词开头的匹配得分更高，间隔越大、候选越长得分越低
+// 返回 (得分, 匹配字符的下标)
+fn fuzzy_match(pattern: &str, candidate: &str, case_sensitive: bool) -> Option<(i64, Vec<usize>)> {
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(normalize)
+        .collect();
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score: i64 = 0;
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut previous: Option<usize> = None;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if positions.len() == pattern.len() {
+            break;
+        }
+        if normalize(c) != pattern[positions.len()] {
+            continue;
+        }
+
+        let mut char_score = 1;
+        if previous.map(|p| p + 1 == i).unwrap_or(false) {
+            char_score += 5;
+        }
+        let at_boundary = i == 0
+            || matches!(chars[i - 1], '/' | '\\' | '_' | '-' | '.' | ' ')
+            || (chars[i - 1].is_lowercase() && c.is_uppercase());
+        if at_boundary {
+            char_score += 3;
+        }
+        if let Some(p) = previous {
+            score -= (i - p - 1).min(5) as i64;
+        }
+
+        score += char_score;
+        positions.push(i);
+        previous = Some(i);
+    }
+
+    if positions.len() < pattern.len() {
+        return None;
+    }
+    score -= chars.len() as i64 / 10;
+    Some((score, positions))
+}
+
+// 匹配文件名或相对路径 (以 '/' 分隔)
+fn match_name(display_path: String, relative_path: &str, re: &Regex, config: &SearchConfig, keyword: &str) -> Option<SearchResult> {
+    let text = match config.mode {
+        SearchMode::FileName => relative_path.rsplit('/').next().unwrap_or(relative_path).to_string(),
+        _ => relative_path.to_string(),
+    };
+    if text.is_empty() {
+        return None;
+    }
+
+    let (score, ranges) = if config.fuzzy {
+        let (score, positions) = fuzzy_match(keyword, &text, config.case_sensitive)?;
+        // 字符下标转换为 UTF-16 偏移，并合并相邻的字符
+        let utf16: Vec<usize> = text
+            .chars()
+            .scan(0, |offset, c| {
+                let start = *offset;
+                *offset += c.len_utf16();
+                Some(start)
+            })
+            .collect();
+        let chars: Vec<char> = text.chars().collect();
+        let mut ranges: Vec<[usize; 2]> = Vec::new();
+        for i in positions {
+            let start = utf16[i];
+            let end = start + chars[i].len_utf16();
+            match ranges.last_mut() {
+                Some(last) if last[1] == start => last[1] = end,
+                _ => ranges.push([start, end]),
+            }
+        }
+        (score, ranges)
+    } else {
+        let ranges: Vec<[usize; 2]> = find_submatches(re, &text)
+            .into_iter()
+            .map(|m| [m.start_utf16, m.end_utf16])
+            .collect();
+        if ranges.is_empty() {
+            return None;
+        }
+        (0, ranges)
+    };
+
+    Some(SearchResult {
+        path: display_path,
+        result: Vec::new(),
+        matches: Vec::new(),
+        name_match: Some(NameMatch { text, score, ranges }),
+        json_matches: Vec::new(),
+    })
+}
+
+// 找出一行中的所有匹配
+pub(crate) fn find_submatches(re: &Regex, line: &str) -> Vec<SubMatch> {
+    let mut submatches = Vec::new();
+    // 逐步累加 UTF-16 偏移，避免每次都从行首重新计算
+    let mut byte_pos = 0;
+    let mut utf16_pos = 0;
+    for m in re.find_iter(line) {
+        utf16_pos += line[byte_pos..m.start()].encode_utf16().count();
+        let start_utf16 = utf16_pos;
+        utf16_pos += m.as_str().encode_utf16().count();
+        byte_pos = m.end();
+        submatches.push(SubMatch {
+            start: m.start(),
+            end: m.end(),
+            start_utf16,
+            end_utf16: utf16_pos,
+        });
+    }
+    submatches
+}
+
+// 单次搜索共享的匹配参数
+pub(crate) struct Matcher<'a> {
+    pub(crate) re: &'a Regex,
+    pub(crate) config: &'a SearchConfig,
+    pub(crate) json_path: Option<&'a JsonPath>,
+    pub(crate) keyword: &'a str,
+    pub(crate) cancelled: &'a AtomicBool,
+}
+
+impl Matcher<'_> {
+    /// 按搜索模式匹配一个条目 (普通文件或压缩包内的文件)
+    /// relative_path 用于文件名匹配和扩展名判断，load 只在需要内容时调用
+    /// 返回的 bool 表示是否因 max_matches 截断；Err 表示内容无法读取或解码
+    pub(crate) fn search_entry(
+        &self,
+        display_path: String,
+        relative_path: &str,
+        load: impl FnOnce() -> Result<Option<String>, String>,
+    ) -> Result<Option<(SearchResult, bool)>, String> {
+        let config = self.config;
+        match config.mode {
+            SearchMode::FileName | SearchMode::Path => {
+                Ok(match_name(display_path, relative_path, self.re, config, self.keyword).map(|r| (r, false)))
+            }
+            SearchMode::Json => {
+                // 只解析 .json 文件
+                if !relative_path.to_lowercase().ends_with(".json") {
+                    return Ok(None);
+                }
+                match load()? {
+                    Some(text) => search_json_text(display_path, &text, self.re, self.json_path, config),
+                    None => Ok(None),
+                }
             }
+            SearchMode::Content => match load()? {
+                Some(text) => Ok(search_text(display_path, &text, self.re, config, self.cancelled)),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+// 在文本内容中搜索
+fn search_text(
+    display_path: String,
+    text: &str,
+    re: &Regex,
+    config: &SearchConfig,
+    cancelled: &AtomicBool,
+) -> Option<(SearchResult, bool)> {
+    if cancelled.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let (matches, truncated) = if config.multiline {
+        search_multiline(text, re, config)
+    } else {
+        search_lines(text, re, config, cancelled)
+    };
+
+    if matches.is_empty() || cancelled.load(Ordering::Relaxed) {
+        None
+    } else {
+        Some((
+            SearchResult {
+                path: display_path,
+                result: matches
+                    .iter()
+                    .map(|m| format!("{}: {}", m.line_number, m.line.trim()))
+                    .collect(),
+                matches,
+                name_match: None,
+                json_matches: Vec::new(),
+            },
+            truncated,
+        ))
+    }
+}
+
+// 在 JSON 文档的字符串值中搜索
+fn search_json_text(
+    display_path: String,
+    text: &str,
+    re: &Regex,
+    json_path: Option<&JsonPath>,
+    config: &SearchConfig,
+) -> Result<Option<(SearchResult, bool)>, String> {
+    let (json_matches, truncated) = search_json::search_json(text, re, json_path, config.max_matches_per_file)?;
+    if json_matches.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((
+        SearchResult {
+            path: display_path,
+            result: json_matches
+                .iter()
+                .map(|m| format!("{}: {}", m.pointer(), m.value().trim()))
+                .collect(),
+            matches: Vec::new(),
+            name_match: None,
+            json_matches,
+        },
+        truncated,
+    )))
+}
+
+// 逐行匹配
+fn search_lines(text: &str, re: &Regex, config: &SearchConfig, cancelled: &AtomicBool) -> (Vec<LineMatch>, bool) {
+    let mut matches: Vec<LineMatch> = Vec::new();
+    let mut truncated = false;
+    // 最近读过的行，作为后续匹配的前置上下文
+    let mut before: VecDeque<ContextLine> = VecDeque::with_capacity(config.context_before);
+
+    for (index, content) in text.lines().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        let line_number = index + 1;
+
+        // 为仍缺少后置上下文的匹配补充当前行
+        let mut waiting_for_context = false;
+        for m in matches.iter_mut().rev() {
+            if line_number - m.line_number > config.context_after {
+                break;
+            }
+            m.after.push(ContextLine {
+                line_number,
+                line: content.to_string(),
+            });
+            waiting_for_context |= line_number - m.line_number < config.context_after;
+        }
+
+        if !truncated {
+            let submatches = find_submatches(re, content);
+            if !submatches.is_empty() {
+                if config.max_matches_per_file.map(|max| matches.len() >= max).unwrap_or(false) {
+                    truncated = true;
+                } else {
+                    matches.push(LineMatch {
+                        line_number,
+                        end_line_number: line_number,
+                        line: content.to_string(),
+                        submatches,
+                        before: before.iter().cloned().collect(),
+                        after: Vec::new(),
+                    });
+                    waiting_for_context = config.context_after > 0;
+                }
+            }
+        }
+
+        // 达到上限后只继续读取到最后一个匹配的后置上下文补全为止
+        if truncated && !waiting_for_context {
+            break;
+        }
+
+        if config.context_before > 0 {
+            if before.len() == config.context_before {
+                before.pop_front();
+            }
+            before.push_back(ContextLine {
+                line_number,
+                line: content.to_string(),
+            });
+        }
+    }
+
+    (matches, truncated)
+}
+
+// 多行模式：在整个文件上匹配，涉及相同行的匹配合并为一个 LineMatch
+fn search_multiline(text: &str, re: &Regex, config: &SearchConfig) -> (Vec<LineMatch>, bool) {
+    // 每一行的起始字节偏移
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+    let line_end = |index: usize| line_starts.get(index + 1).copied().unwrap_or(text.len());
+    let line_text = |index: usize| text[line_starts[index]..line_end(index)].trim_end_matches(['\n', '\r']);
 
-            if is_binary_file(path) {
-                continue;
+    // (起始行, 结束行, 匹配的字节范围)，行号从 0 开始
+    type Group = (usize, usize, Vec<(usize, usize)>);
+    let mut groups: Vec<Group> = Vec::new();
+    let mut truncated = false;
+    for m in re.find_iter(text) {
+        let first = line_of(m.start());
+        // 以换行结尾的匹配不算入下一行
+        let last = line_of(m.end().saturating_sub(1).max(m.start()));
+        match groups.last_mut() {
+            Some(group) if group.1 >= first => {
+                group.1 = group.1.max(last);
+                group.2.push((m.start(), m.end()));
             }
+            _ => {
+                if config.max_matches_per_file.map(|max| groups.len() >= max).unwrap_or(false) {
+                    truncated = true;
+                    break;
+                }
+                groups.push((first, last, vec![(m.start(), m.end())]));
+            }
+        }
+    }
+
+    let line_count = line_starts.len();
+    let matches = groups
+        .into_iter()
+        .map(|(first, last, ranges)| {
+            let block_start = line_starts[first];
+            let block = text[block_start..line_end(last)].trim_end_matches(['\n', '\r']);
+            let submatches = ranges
+                .into_iter()
+                .map(|(start, end)| {
+                    // 匹配末尾的换行不在块内
+                    let start = (start - block_start).min(block.len());
+                    let end = (end - block_start).min(block.len());
+                    let start_utf16 = block.get(..start).map(|s| s.encode_utf16().count()).unwrap_or(0);
+                    let end_utf16 = block.get(..end).map(|s| s.encode_utf16().count()).unwrap_or(start_utf16);
+                    SubMatch {
+                        start,
+                        end,
+                        start_utf16,
+                        end_utf16,
+                    }
+                })
+                .collect();
+            let context = |range: std::ops::Range<usize>| {
+                range
+                    .map(|i| ContextLine {
+                        line_number: i + 1,
+                        line: line_text(i).to_string(),
+                    })
+                    .collect()
+            };
+            LineMatch {
+                line_number: first + 1,
+                end_line_number: last + 1,
+                line: block.to_string(),
+                submatches,
+                before: context(first.saturating_sub(config.context_before)..first),
+                after: context(last + 1..(last + 1 + config.context_after).min(line_count)),
+            }
+        })
+        .collect();
+
+    (matches, truncated)
+}
 
-            let file = match File::open(path) {
-                Ok(f) => f,
-                Err(_) => continue,
+// 遍历目录并搜索，每个有匹配的文件先交给 on_result (用于推送 "search-result" 事件)
+// 返回 (结果, 是否截断, 无法读取的文件)
+fn run_search(
+    walker: &WalkBuilder,
+    matcher: &Matcher,
+    overrides: &Override,
+    on_result: impl Fn(&SearchResult) + Sync,
+) -> (Vec<SearchResult>, bool, Vec<UnreadableFile>) {
+    let config = matcher.config;
+    // (结果, 匹配总数, 是否截断)
+    let collected: Mutex<(Vec<SearchResult>, usize, bool)> = Mutex::new((Vec::new(), 0, false));
+    let unreadable: Mutex<Vec<UnreadableFile>> = Mutex::new(Vec::new());
+
+    walker.build_parallel().run(|| {
+        let collected = &collected;
+        let unreadable = &unreadable;
+        let on_result = &on_result;
+        Box::new(move |entry| {
+            if matcher.cancelled.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => {
+                    unreadable.lock().unwrap().push(UnreadableFile::new(None, e.to_string()));
+                    return WalkState::Continue;
+                }
             };
-            let reader = BufReader::new(file);
-            let mut matches: Vec<String> = Vec::new();
-
-            // 逐行读取匹配
-            for (index, line) in reader.lines().enumerate() {
-                if let Ok(content) = line {
-                    if re.is_match(&content) {
-                        // 返回格式： "行号: 内容" (去除了首尾空格)
-                        matches.push(format!("{}: {}", index + 1, content.trim()));
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                return WalkState::Continue;
+            }
+
+            // 记录一个条目的结果，返回 false 表示已达到总数上限
+            let record = |mut result: SearchResult, file_truncated: bool| -> bool {
+                let mut guard = collected.lock().unwrap();
+                let (results, total, truncated) = &mut *guard;
+                *truncated |= file_truncated;
+
+                // 检查总数上限；文件名模式需要先排序，最后再截断
+                let mut more = true;
+                let ranked = matches!(config.mode, SearchMode::FileName | SearchMode::Path);
+                if let (Some(max), false) = (config.max_results, ranked) {
+                    let remaining = max.saturating_sub(*total);
+                    if result.len() >= remaining {
+                        *truncated |= result.limit_to(remaining);
+                        more = false;
                     }
                 }
+                // 结果全部被丢弃时，上面已经标记了 truncated
+                if result.len() == 0 {
+                    return more;
+                }
+
+                *total += result.len();
+                on_result(&result);
+                results.push(result);
+                more
+            };
+            let report = |path: String, reason: String| {
+                unreadable.lock().unwrap().push(UnreadableFile::new(Some(path), reason));
+            };
+
+            let path = entry.path();
+            let display_path = path.to_string_lossy().to_string();
+            let relative_path = path
+                .strip_prefix(&config.target_dir)
+                .ok()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            // 压缩包：target_dir 本身是压缩包，或开启了 search_archives
+            let is_target = path == Path::new(&config.target_dir);
+            if search_archive::is_archive(path) && (is_target || config.search_archives) {
+                return match search_archive::search_archive(path, &relative_path, matcher, overrides, record, report) {
+                    Ok(true) => WalkState::Continue,
+                    Ok(false) => WalkState::Quit,
+                    Err(reason) => {
+                        report(display_path, reason);
+                        WalkState::Continue
+                    }
+                };
             }
 
-            if !matches.is_empty() {
-                results.push(SearchResult {
-                    path: path_str.to_string(),
-                    result: matches,
-                });
+            let found = matcher.search_entry(display_path.clone(), &relative_path, || read_text_file(path));
+            match found {
+                Ok(Some((result, file_truncated))) => {
+                    if record(result, file_truncated) {
+                        WalkState::Continue
+                    } else {
+                        WalkState::Quit
+                    }
+                }
+                Ok(None) => WalkState::Continue,
+                Err(reason) => {
+                    report(display_path, reason);
+                    WalkState::Continue
+                }
             }
+        })
+    });
+
+    let (mut results, _, mut truncated) = collected.into_inner().unwrap();
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    // 文件名模式按得分排序，方便快速打开
+    if matches!(config.mode, SearchMode::FileName | SearchMode::Path) {
+        results.sort_by_key(|r| std::cmp::Reverse(r.name_match.as_ref().map(|m| m.score).unwrap_or(0)));
+        if let Some(max) = config.max_results {
+            truncated |= results.len() > max;
+            results.truncate(max);
+        }
+    }
+
+    let mut unreadable = unreadable.into_inner().unwrap();
+    unreadable.sort_by(|a, b| a.path.cmp(&b.path));
+    (results, truncated, unreadable)
+}
+
+/// 并行搜索目录下的所有文件
+/// search_id 可由前端指定，否则自动生成；开始时发送 "search-started" 事件，
+/// 每个有匹配的文件立即通过 "search-result" 事件推送，全部完成后发送 "search-finished" 事件。
+/// 同一调用方 (窗口 + caller) 发起新搜索时，之前未完成的搜索会被自动取消
+#[tauri::command]
+pub async fn search_in_files(
+    app: AppHandle,
+    window: tauri::Window,
+    state: State<'_, SearchState>,
+    keyword: String,
+    config: SearchConfig,
+    search_id: Option<String>,
+    caller: Option<String>,
+) -> Result<SearchResponse, String> {
+    let search_id = search_id.unwrap_or_else(new_search_id);
+    let caller = format!("{}:{}", window.label(), caller.unwrap_or_default());
+
+    if keyword.is_empty() {
+        // 清空输入时同样取消该调用方之前的搜索
+        state.begin(&search_id, &caller);
+        state.finish(&search_id, &caller);
+        return Ok(SearchResponse {
+            search_id,
+            results: vec![],
+            truncated: false,
+            cancelled: false,
+            unreadable: vec![],
+        });
+    }
+
+    let re = build_regex(&keyword, &config)?;
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let walker = build_walker(&config, &data_dir)?;
+    let overrides = build_overrides(&config)?;
+    let json_path = match config.json_path.as_deref().filter(|p| !p.trim().is_empty()) {
+        Some(path) => Some(JsonPath::parse(path.trim())?),
+        None => None,
+    };
+    let flag = state.begin(&search_id, &caller);
+    let _ = app.emit(
+        "search-started",
+        SearchStartedEvent {
+            search_id: &search_id,
+            caller: &caller,
+        },
+    );
+
+    let id = search_id.clone();
+    let task = tauri::async_runtime::spawn_blocking(move || {
+        let matcher = Matcher {
+            re: &re,
+            config: &config,
+            json_path: json_path.as_ref(),
+            keyword: keyword.as_str(),
+            cancelled: flag.as_ref(),
+        };
+        let (results, truncated, unreadable) = run_search(&walker, &matcher, &overrides, |result| {
+            let _ = app.emit(
+                "search-result",
+                SearchResultEvent {
+                    search_id: &id,
+                    result,
+                },
+            );
+        });
+
+        let cancelled = flag.load(Ordering::SeqCst);
+        let _ = app.emit(
+            "search-finished",
+            SearchFinishedEvent {
+                search_id: &id,
+                file_count: results.len(),
+                truncated,
+                cancelled,
+                unreadable: &unreadable,
+            },
+        );
+        (results, truncated, cancelled, unreadable)
+    });
+
+    let outcome = task.await;
+    state.finish(&search_id, &caller);
+    let (results, truncated, cancelled, unreadable) = outcome.map_err(|e| e.to_string())?;
+
+    Ok(SearchResponse {
+        search_id,
+        results,
+        truncated,
+        cancelled,
+        unreadable,
+    })
+}
+
+// 取消正在进行的搜索，返回是否找到该搜索
+#[tauri::command]
+pub fn cancel_search(state: State<'_, SearchState>, search_id: String) -> bool {
+    match state.active.lock().unwrap().get(&search_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            true
         }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_match(line_number: usize) -> LineMatch {
+        LineMatch {
+            line_number,
+            end_line_number: line_number,
+            line: format!("match {}", line_number),
+            submatches: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    fn content_result(count: usize) -> SearchResult {
+        SearchResult {
+            path: "a.txt".into(),
+            result: (1..=count).map(|n| format!("{}: match {}", n, n)).collect(),
+            matches: (1..=count).map(line_match).collect(),
+            name_match: None,
+            json_matches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reaching_the_limit_exactly_is_not_truncation() {
+        let mut result = content_result(3);
+        assert!(!result.limit_to(3));
+        assert_eq!(result.len(), 3);
+
+        assert!(result.limit_to(2));
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.result.len(), 2);
+    }
+
+    #[test]
+    fn file_name_results_count_once() {
+        let mut result = content_result(0);
+        result.name_match = Some(NameMatch {
+            text: "a.txt".into(),
+            score: 10,
+            ranges: vec![[0, 1]],
+        });
+        assert_eq!(result.len(), 1);
+        assert!(!result.limit_to(1));
+        assert!(result.limit_to(0));
+        assert_eq!(result.len(), 0);
+    }
+
+    fn name_config(mode: &str) -> SearchConfig {
+        serde_json::from_value(serde_json::json!({
+            "case_sensitive": false,
+            "whole_word": false,
+            "is_regex": false,
+            "target_dir": ".",
+            "mode": mode,
+            "fuzzy": true,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fuzzy_match_requires_characters_in_order() {
+        let (_, positions) = fuzzy_match("abc", "a_b_c.txt", false).unwrap();
+        assert_eq!(positions, vec![0, 2, 4]);
+        assert!(fuzzy_match("cba", "a_b_c.txt", false).is_none());
+        assert!(fuzzy_match("abcd", "abc", false).is_none());
+    }
+
+    #[test]
+    fn fuzzy_match_case_and_whitespace() {
+        assert!(fuzzy_match("ABC", "abc.rs", false).is_some());
+        assert!(fuzzy_match("ABC", "abc.rs", true).is_none());
+        assert!(fuzzy_match("a b", "ab", false).is_some());
+        assert!(fuzzy_match("  ", "ab", false).is_none());
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_consecutive_and_boundary_matches() {
+        let score = |pattern, candidate| fuzzy_match(pattern, candidate, false).unwrap().0;
+        assert!(score("main", "main.rs") > score("main", "my_app_index_n.rs"));
+        assert!(score("fb", "foo_bar") > score("fb", "afxxbx"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        // 其他条件相同时，较短的候选得分更高
+        assert!(score("ab", "ab") > score("ab", "ab_with_a_long_suffix"));
+    }
+
+    #[test]
+    fn fuzzy_name_ranges_use_utf16_offsets() {
+        let re = Regex::new("unused").unwrap();
+        let config = name_config("file_name");
+        let result = match_name("p".into(), "dir/测试😀ab.txt", &re, &config, "😀ab").unwrap();
+        let name = result.name_match.unwrap();
+        assert_eq!(name.text, "测试😀ab.txt");
+        assert_eq!(name.ranges, vec![[2, 6]]);
+    }
+
+    #[test]
+    fn file_name_mode_ignores_directories() {
+        let re = Regex::new("unused").unwrap();
+        let file_name = name_config("file_name");
+        assert!(match_name("p".into(), "docs/readme.md", &re, &file_name, "docs").is_none());
+        let path = name_config("path");
+        assert!(match_name("p".into(), "docs/readme.md", &re, &path, "docs").is_some());
     }
 
-    Ok(results)
+    fn write_test_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("search-read-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_text_file_detects_encodings() {
+        let utf8 = write_test_file("utf8.txt", "宝剑 sword".as_bytes());
+        assert_eq!(
+            read_text_file(&utf8).unwrap().as_deref(),
+            Some("宝剑 sword")
+        );
+
+        let mut utf16: Vec<u8> = vec![0xFF, 0xFE];
+        utf16.extend("宝剑".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        let bom = write_test_file("utf16-bom.txt", &utf16);
+        assert_eq!(read_text_file(&bom).unwrap().as_deref(), Some("宝剑"));
+
+        let no_bom: Vec<u8> = "hello"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        let no_bom = write_test_file("utf16-le.txt", &no_bom);
+        assert_eq!(read_text_file(&no_bom).unwrap().as_deref(), Some("hello"));
+
+        let text = "这是一段用国标码保存的中文文本，用来检查编码识别是否正确。";
+        let (gbk, _, _) = encoding_rs::GBK.encode(text);
+        let gbk = write_test_file("gbk.txt", &gbk);
+        assert_eq!(read_text_file(&gbk).unwrap().as_deref(), Some(text));
+    }
+
+    #[test]
+    fn binary_files_are_detected_from_the_first_bytes() {
+        let mut binary = vec![0x7F, b'E', b'L', b'F', 0, 1, 0, 0, 2, 0, 0, 0];
+        binary.resize(SNIFF_LEN * 4, 0xAB);
+        assert_eq!(read_text(binary.as_slice(), 16).unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_text_files_are_rejected() {
+        let text = [b'a'; 64];
+        assert!(read_text(text.as_slice(), 63).is_err());
+        assert_eq!(
+            read_text(text.as_slice(), 64).unwrap().map(|t| t.len()),
+            Some(64)
+        );
+    }
+
+    #[test]
+    fn search_skips_secret_files_in_data_dir() {
+        let data_dir = std::env::temp_dir().join(format!("search-secrets-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+        for path in [
+            "secrets.json",
+            "secret_profiles/work.json",
+            "secrets_audit.jsonl",
+            "replace_undo/abc.json",
+            "notes/a.txt",
+        ] {
+            let path = data_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "OPENAI_KEY sk-live-123").unwrap();
+        }
+
+        let config: SearchConfig = serde_json::from_value(serde_json::json!({
+            "case_sensitive": false,
+            "whole_word": false,
+            "is_regex": false,
+            "target_dir": data_dir.to_string_lossy(),
+        }))
+        .unwrap();
+        let re = build_regex("sk-live", &config).unwrap();
+        let cancelled = AtomicBool::new(false);
+        let matcher = Matcher {
+            re: &re,
+            config: &config,
+            json_path: None,
+            keyword: "sk-live",
+            cancelled: &cancelled,
+        };
+        let walker = build_walker(&config, &data_dir).unwrap();
+        let overrides = build_overrides(&config).unwrap();
+        let (results, _, _) = run_search(&walker, &matcher, &overrides, |_| {});
+        let paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec![data_dir.join("notes/a.txt").to_string_lossy()]);
+
+        // 直接以密钥目录为搜索目录同样被拒绝
+        let profiles: SearchConfig = serde_json::from_value(serde_json::json!({
+            "case_sensitive": false,
+            "whole_word": false,
+            "is_regex": false,
+            "target_dir": data_dir.join("secret_profiles").to_string_lossy(),
+        }))
+        .unwrap();
+        assert!(build_walker(&profiles, &data_dir).is_err());
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
 }
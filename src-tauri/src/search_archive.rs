@@ -0,0 +1,184 @@
+// src-tauri/src/search_archive.rs
+
+use crate::search::{self, Matcher, SearchResult};
+use ignore::overrides::Override;
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use zip::ZipArchive;
+
+// 压缩包内文件在结果中的路径分隔符，例如 "backup-20250101.zip!/data/chats/a.json"
+pub(crate) const ARCHIVE_SEPARATOR: &str = "!/";
+
+pub(crate) fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false)
+}
+
+/// 不解压，逐个搜索 zip 中的文件
+/// record 返回 false 表示已达到总数上限，此时停止并返回 Ok(false)
+/// report 记录无法读取的条目；压缩包本身无法打开时返回 Err
+pub(crate) fn search_archive(
+    archive_path: &Path,
+    relative_path: &str,
+    matcher: &Matcher,
+    overrides: &Override,
+    mut record: impl FnMut(SearchResult, bool) -> bool,
+    mut report: impl FnMut(String, String),
+) -> Result<bool, String> {
+    let config = matcher.config;
+    let file = File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Invalid archive: {}", e))?;
+    let archive_display = archive_path.to_string_lossy().to_string();
+
+    for i in 0..archive.len() {
+        if matcher.cancelled.load(Ordering::Relaxed) {
+            return Ok(true);
+        }
+        let mut entry = match archive.by_index(i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report(format!("{}{}#{}", archive_display, ARCHIVE_SEPARATOR, i), e.to_string());
+                continue;
+            }
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let inner = entry.name().replace('\\', "/");
+        let display_path = format!("{}{}{}", archive_display, ARCHIVE_SEPARATOR, inner);
+
+        // 与目录遍历相同的过滤规则，glob 相对于压缩包根目录
+        if !config.include_hidden && inner.split('/').any(|c| c.starts_with('.')) {
+            continue;
+        }
+        if overrides.matched(&inner, false).is_ignore() {
+            continue;
+        }
+        // 头部声明的大小可能不实，读取时还会按同一上限截断
+        let max_size = config.max_file_size.unwrap_or(search::DEFAULT_MAX_FILE_SIZE);
+        if entry.size() > max_size {
+            continue;
+        }
+        // 不递归搜索嵌套的压缩包
+        if is_archive(Path::new(&inner)) {
+            continue;
+        }
+
+        let entry_relative = format!("{}{}{}", relative_path, ARCHIVE_SEPARATOR, inner);
+        let found = matcher.search_entry(display_path.clone(), &entry_relative, || {
+            search::read_text(&mut entry, max_size)
+        });
+        match found {
+            Ok(Some((result, truncated))) => {
+                if !record(result, truncated) {
+                    return Ok(false);
+                }
+            }
+            Ok(None) => {}
+            Err(reason) => report(display_path, reason),
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::io::Write;
+    use std::sync::atomic::AtomicBool;
+    use zip::write::SimpleFileOptions;
+
+    fn write_archive(name: &str, entries: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("search-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (entry, contents) in entries {
+            writer
+                .start_file(*entry, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    // 返回 (命中的条目, 无法读取的条目)
+    fn search(path: &Path, max_file_size: Option<u64>) -> (Vec<String>, Vec<String>) {
+        let config: search::SearchConfig = serde_json::from_value(serde_json::json!({
+            "case_sensitive": false,
+            "whole_word": false,
+            "is_regex": false,
+            "target_dir": path.to_string_lossy(),
+            "max_file_size": max_file_size,
+        }))
+        .unwrap();
+        let re = Regex::new("sword").unwrap();
+        let cancelled = AtomicBool::new(false);
+        let matcher = Matcher {
+            re: &re,
+            config: &config,
+            json_path: None,
+            keyword: "sword",
+            cancelled: &cancelled,
+        };
+        let overrides = search::build_overrides(&config).unwrap();
+
+        let (mut found, mut unreadable) = (Vec::new(), Vec::new());
+        let finished = search_archive(
+            path,
+            "a.zip",
+            &matcher,
+            &overrides,
+            |result, _| {
+                let path = serde_json::to_value(&result).unwrap()["path"]
+                    .as_str()
+                    .unwrap()
+                    .to_string();
+                found.push(path.rsplit(ARCHIVE_SEPARATOR).next().unwrap().to_string());
+                true
+            },
+            |path, _| unreadable.push(path.rsplit(ARCHIVE_SEPARATOR).next().unwrap().to_string()),
+        )
+        .unwrap();
+        assert!(finished);
+        (found, unreadable)
+    }
+
+    #[test]
+    fn searches_text_entries_and_skips_binary_and_nested_archives() {
+        let nested = write_archive("inner.zip", &[("x.txt", b"sword")]);
+        let nested = std::fs::read(nested).unwrap();
+        let path = write_archive(
+            "entries.zip",
+            &[
+                ("notes/a.txt", b"a sword"),
+                ("b.txt", b"a shield"),
+                ("image.bin", b"sword\0\x01\x02"),
+                ("inner.zip", &nested),
+            ],
+        );
+        assert_eq!(
+            search(&path, None),
+            (vec!["notes/a.txt".to_string()], vec![])
+        );
+    }
+
+    #[test]
+    fn oversized_entries_are_skipped() {
+        let large = format!("sword{}", "a".repeat(100));
+        let path = write_archive(
+            "large.zip",
+            &[("small.txt", b"sword"), ("large.txt", large.as_bytes())],
+        );
+        assert_eq!(
+            search(&path, Some(50)),
+            (vec!["small.txt".to_string()], vec![])
+        );
+        assert_eq!(search(&path, None).0.len(), 2);
+    }
+}
@@ -0,0 +1,893 @@
+// src-tauri/src/search_index.rs
+
+use crate::atomic_file;
+use crate::search;
+use crate::search_json;
+use crate::search_semantic::{self, SemanticIndexState};
+use crate::secrets_manager;
+use ignore::WalkBuilder;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+
+// 索引格式变化时递增，旧索引会被丢弃并重建
+const INDEX_VERSION: u32 = 2;
+const INDEX_DIR: &str = "search_index";
+const INDEX_FILE: &str = "index.json";
+// 应用自己维护的目录，不需要被搜索 (密钥文件见 secrets_manager::SECRET_FILES)
+const EXCLUDED_DIRS: &[&str] = &[INDEX_DIR, "replace_undo", "script_scratch"];
+// 超过该大小的文件不建立索引
+const MAX_INDEXED_FILE_SIZE: u64 = 8 * 1024 * 1024;
+const INDEXED_EXTENSIONS: &[&str] = &["json", "jsonl", "txt", "md", "yaml", "yml", "html", "js", "ts", "css"];
+// 文件变化后等待多久再更新索引 (合并连续写入)
+const UPDATE_DELAY: Duration = Duration::from_secs(2);
+// 内存中的索引最多多久写回磁盘一次
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+// 前缀查询最多展开的词数
+const MAX_PREFIX_EXPANSION: usize = 200;
+const DEFAULT_LIMIT: usize = 50;
+// BM25 参数
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Posting {
+    doc: u32,
+    positions: Vec<u32>, // 词在文档中的位置，递增
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DocEntry {
+    path: String,
+    modified: u64, // 修改时间 (毫秒)，用于启动时判断文件是否变化
+    size: u64,
+    length: u32, // 词数
+    terms: Vec<String>, // 文档包含的词，删除文档时用于清理倒排表
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct InvertedIndex {
+    version: u32,
+    next_doc: u32,
+    docs: HashMap<u32, DocEntry>,
+    postings: BTreeMap<String, Vec<Posting>>,
+    total_length: u64,
+    #[serde(skip)]
+    by_path: HashMap<String, u32>,
+}
+
+// 查询中的一个条件，所有条件都需要满足
+#[derive(Debug)]
+enum Clause {
+    Phrase { tokens: Vec<String>, raw: String }, // 单个词也视为长度为 1 的短语
+    Prefix { token: String, raw: String },
+}
+
+impl Clause {
+    fn raw(&self) -> &str {
+        match self {
+            Clause::Phrase { raw, .. } | Clause::Prefix { raw, .. } => raw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexHit {
+    path: String,
+    score: f64,
+    line_number: Option<usize>, // 第一处命中所在的行
+    snippet: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexQueryResponse {
+    hits: Vec<IndexHit>,
+    total: usize, // 命中的文档总数 (截断前)
+    ready: bool,  // 初次建立索引尚未完成时结果可能不完整
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexStatus {
+    ready: bool,
+    documents: usize,
+    terms: usize,
+}
+
+pub struct SearchIndexState {
+    index: Arc<RwLock<InvertedIndex>>,
+    ready: Arc<AtomicBool>,
+    // 等待更新的路径，由文件监听器写入
+    pending: Arc<Mutex<HashSet<PathBuf>>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl SearchIndexState {
+    pub fn new() -> Self {
+        Self {
+            index: Arc::new(RwLock::new(InvertedIndex::default())),
+            ready: Arc::new(AtomicBool::new(false)),
+            pending: Arc::new(Mutex::new(HashSet::new())),
+            watcher: Mutex::new(None),
+        }
+    }
+}
+
+// --- 分词 ---
+
+// 中日韩文字没有空格分词，按字符二元组 (bigram) 切分
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3040..=0x30FF // 平假名、片假名
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xAC00..=0xD7AF // 韩文
+            | 0xF900..=0xFAFF
+            | 0x20000..=0x2FA1F
+    )
+}
+
+/// 分词：字母数字按连续片段切分并转为小写，中日韩文字切分为重叠的二元组 (单字片段保留单字)
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    tokenize_with_chars(text, |_, _| {})
+}
+
+// 分词，同时报告多字片段中的每个字及其所在二元组的位置
+// 索引时额外记录单字，单字查询 (如 "剑") 才能命中 "宝剑"
+fn tokenize_with_chars(text: &str, mut on_char: impl FnMut(char, u32)) -> Vec<String> {
+    fn flush_cjk(run: &mut Vec<char>, tokens: &mut Vec<String>, on_char: &mut impl FnMut(char, u32)) {
+        if run.len() == 1 {
+            tokens.push(run[0].to_string());
+        } else {
+            let base = tokens.len();
+            for (i, &c) in run.iter().enumerate() {
+                // 最后一个字归入最后一个二元组
+                on_char(c, (base + i.min(run.len() - 2)) as u32);
+            }
+            tokens.extend(run.windows(2).map(|pair| pair.iter().collect()));
+        }
+        run.clear();
+    }
+
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            if !cjk.is_empty() {
+                flush_cjk(&mut cjk, &mut tokens, &mut on_char);
+            }
+            word.extend(c.to_lowercase());
+        } else {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            if !cjk.is_empty() {
+                flush_cjk(&mut cjk, &mut tokens, &mut on_char);
+            }
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    if !cjk.is_empty() {
+        flush_cjk(&mut cjk, &mut tokens, &mut on_char);
+    }
+    tokens
+}
+
+/// 解析查询：空格分隔的词之间为“与”关系，"..." 为短语，以 * 结尾为前缀查询
+/// 中文等分词后得到多个词的片段自动按短语处理
+fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let mut push = |text: &str, quoted: bool| {
+        let prefix = !quoted && text.len() > 1 && text.ends_with('*');
+        let raw = text.trim_end_matches('*');
+        let tokens = tokenize(raw);
+        if tokens.is_empty() {
+            return;
+        }
+        let raw = raw.to_lowercase();
+        if prefix && tokens.len() == 1 {
+            clauses.push(Clause::Prefix {
+                token: tokens.into_iter().next().unwrap(),
+                raw,
+            });
+        } else {
+            clauses.push(Clause::Phrase { tokens, raw });
+        }
+    };
+
+    let mut rest = query;
+    while let Some(start) = rest.find('"') {
+        rest[..start].split_whitespace().for_each(|word| push(word, false));
+        let after = &rest[start + 1..];
+        match after.find('"') {
+            Some(end) => {
+                push(&after[..end], true);
+                rest = &after[end + 1..];
+            }
+            // 未闭合的引号视为到结尾
+            None => {
+                push(after, true);
+                rest = "";
+            }
+        }
+    }
+    rest.split_whitespace().for_each(|word| push(word, false));
+    clauses
+}
+
+// --- 索引 ---
+
+impl InvertedIndex {
+    fn rebuild_lookup(&mut self) {
+        self.by_path = self
+            .docs
+            .iter()
+            .map(|(id, doc)| (doc.path.clone(), *id))
+            .collect();
+    }
+
+    fn remove(&mut self, path: &str) -> bool {
+        let id = match self.by_path.remove(path) {
+            Some(id) => id,
+            None => return false,
+        };
+        if let Some(doc) = self.docs.remove(&id) {
+            self.total_length -= doc.length as u64;
+            for term in doc.terms {
+                if let Some(list) = self.postings.get_mut(&term) {
+                    list.retain(|p| p.doc != id);
+                    if list.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // 目录被删除或移动时，移除其下的所有文档
+    fn remove_prefix(&mut self, dir: &str) -> bool {
+        let prefix = format!("{}{}", dir.trim_end_matches(std::path::MAIN_SEPARATOR), std::path::MAIN_SEPARATOR);
+        let paths: Vec<String> = self
+            .by_path
+            .keys()
+            .filter(|p| p.starts_with(&prefix))
+            .cloned()
+            .collect();
+        paths.iter().fold(false, |changed, p| self.remove(p) | changed)
+    }
+
+    fn insert(&mut self, path: String, modified: u64, size: u64, text: &str) {
+        self.remove(&path);
+
+        let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+        let tokens = tokenize_with_chars(text, |c, i| positions.entry(c.to_string()).or_default().push(i));
+        for (i, token) in tokens.iter().enumerate() {
+            positions.entry(token.clone()).or_default().push(i as u32);
+        }
+        // 单字和单字片段的位置交错写入，需要重新排序
+        positions.values_mut().for_each(|p| p.sort_unstable());
+
+        let id = self.next_doc;
+        self.next_doc += 1;
+        let terms: Vec<String> = positions.keys().cloned().collect();
+        for (term, positions) in positions {
+            self.postings
+                .entry(term)
+                .or_default()
+                .push(Posting { doc: id, positions });
+        }
+        self.total_length += tokens.len() as u64;
+        self.by_path.insert(path.clone(), id);
+        self.docs.insert(
+            id,
+            DocEntry {
+                path,
+                modified,
+                size,
+                length: tokens.len() as u32,
+                terms,
+            },
+        );
+    }
+
+    // 移除不应被索引的文档 (例如旧版本索引过的密钥文件)
+    fn remove_excluded(&mut self, data_dir: &Path) -> bool {
+        let paths: Vec<String> = self
+            .by_path
+            .keys()
+            .filter(|p| !is_indexable(data_dir, Path::new(p)))
+            .cloned()
+            .collect();
+        paths.iter().fold(false, |changed, p| self.remove(p) | changed)
+    }
+
+    fn is_current(&self, path: &str, modified: u64, size: u64) -> bool {
+        self.by_path
+            .get(path)
+            .and_then(|id| self.docs.get(id))
+            .map(|doc| doc.modified == modified && doc.size == size)
+            .unwrap_or(false)
+    }
+
+    // 某个条件在每个文档中的出现次数
+    fn clause_frequencies(&self, clause: &Clause) -> HashMap<u32, u32> {
+        let mut freqs = HashMap::new();
+        match clause {
+            Clause::Prefix { token, .. } => {
+                let expanded = self
+                    .postings
+                    .range(token.clone()..)
+                    .take_while(|(term, _)| term.starts_with(token.as_str()))
+                    .take(MAX_PREFIX_EXPANSION);
+                for (_, list) in expanded {
+                    for posting in list {
+                        *freqs.entry(posting.doc).or_insert(0) += posting.positions.len() as u32;
+                    }
+                }
+            }
+            Clause::Phrase { tokens, .. } => {
+                let lists: Option<Vec<HashMap<u32, &Vec<u32>>>> = tokens
+                    .iter()
+                    .map(|t| {
+                        self.postings
+                            .get(t)
+                            .map(|list| list.iter().map(|p| (p.doc, &p.positions)).collect())
+                    })
+                    .collect();
+                let lists = match lists {
+                    Some(lists) => lists,
+                    None => return freqs,
+                };
+                for (doc, first) in &lists[0] {
+                    // 后续每个词都需要出现在紧随其后的位置
+                    let count = first
+                        .iter()
+                        .filter(|&&start| {
+                            lists[1..].iter().enumerate().all(|(i, list)| {
+                                list.get(doc)
+                                    .map(|positions| positions.binary_search(&(start + i as u32 + 1)).is_ok())
+                                    .unwrap_or(false)
+                            })
+                        })
+                        .count();
+                    if count > 0 {
+                        freqs.insert(*doc, count as u32);
+                    }
+                }
+            }
+        }
+        freqs
+    }
+
+    // BM25 排序，所有条件都需要命中
+    fn query(&self, clauses: &[Clause]) -> Vec<(u32, f64)> {
+        if clauses.is_empty() || self.docs.is_empty() {
+            return Vec::new();
+        }
+        let total_docs = self.docs.len() as f64;
+        let avg_length = (self.total_length as f64 / total_docs).max(1.0);
+
+        let mut scores: Option<HashMap<u32, f64>> = None;
+        for clause in clauses {
+            let freqs = self.clause_frequencies(clause);
+            let df = freqs.len() as f64;
+            let idf = (1.0 + (total_docs - df + 0.5) / (df + 0.5)).ln();
+
+            let mut next = HashMap::new();
+            for (doc, tf) in freqs {
+                let previous = match &scores {
+                    Some(scores) => match scores.get(&doc) {
+                        Some(score) => *score,
+                        None => continue,
+                    },
+                    None => 0.0,
+                };
+                let length = self.docs.get(&doc).map(|d| d.length).unwrap_or(0) as f64;
+                let tf = tf as f64;
+                let score = idf * tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length / avg_length));
+                next.insert(doc, previous + score);
+            }
+            scores = Some(next);
+        }
+
+        let mut ranked: Vec<(u32, f64)> = scores.unwrap_or_default().into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+// --- 文件读取与持久化 ---
+
+fn get_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+// 索引文件所在目录，语义索引也保存在这里
+pub(crate) fn get_index_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = get_data_dir(app)?.join(INDEX_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn get_index_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(get_index_dir(app)?.join(INDEX_FILE))
+}
+
+// 应用内部目录和密钥文件，全文搜索、替换和索引都会跳过
+pub(crate) fn is_excluded_dir(data_dir: &Path, path: &Path) -> bool {
+    EXCLUDED_DIRS
+        .iter()
+        .chain(secrets_manager::SECRET_FILES)
+        .any(|dir| path.starts_with(data_dir.join(dir)))
+}
+
+// 是否应该为该文件建立索引：跳过隐藏目录 (如 .git) 和应用内部目录
+pub(crate) fn is_indexable(data_dir: &Path, path: &Path) -> bool {
+    let relative = match path.strip_prefix(data_dir) {
+        Ok(r) => r,
+        Err(_) => return false,
+    };
+    let mut components = relative.components().map(|c| c.as_os_str().to_string_lossy());
+    if components.any(|c| c.starts_with('.')) || is_excluded_dir(data_dir, path) {
+        return false;
+    }
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            INDEXED_EXTENSIONS.contains(&ext.as_str())
+        })
+        .unwrap_or(false)
+}
+
+pub(crate) fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    if !meta.is_file() {
+        return None;
+    }
+    let modified = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    Some((modified, meta.len()))
+}
+
+// 读取需要建立索引的文本，JSON 文件只取字符串值
+fn read_document(path: &Path) -> Option<String> {
+    let text = search::read_text_file(path).ok()??;
+    let is_json = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    if is_json {
+        if let Ok(values) = search_json::extract_text(&text) {
+            return Some(values);
+        }
+    }
+    Some(text)
+}
+
+// 更新单个路径的索引，返回索引是否有变化
+fn update_path(index: &RwLock<InvertedIndex>, data_dir: &Path, path: &Path) -> bool {
+    let key = path.to_string_lossy().to_string();
+    match file_stamp(path) {
+        Some((modified, size)) if is_indexable(data_dir, path) && size <= MAX_INDEXED_FILE_SIZE => {
+            if index.read().unwrap().is_current(&key, modified, size) {
+                return false;
+            }
+            // 读文件时不持有锁，避免阻塞查询
+            match read_document(path) {
+                Some(text) => {
+                    index.write().unwrap().insert(key, modified, size, &text);
+                    true
+                }
+                None => index.write().unwrap().remove(&key),
+            }
+        }
+        Some(_) => index.write().unwrap().remove(&key),
+        // 移入的目录：逐个更新其中的文件
+        None if path.is_dir() => files_under(path)
+            .iter()
+            .fold(false, |changed, file| update_path(index, data_dir, file) | changed),
+        // 文件或目录已被删除
+        None => {
+            let mut index = index.write().unwrap();
+            index.remove(&key) | index.remove_prefix(&key)
+        }
+    }
+}
+
+fn load_index(app: &AppHandle) -> InvertedIndex {
+    let loaded = get_index_path(app)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|contents| serde_json::from_str::<InvertedIndex>(&contents).map_err(|e| e.to_string()));
+    match loaded {
+        Ok(mut index) if index.version == INDEX_VERSION => {
+            index.rebuild_lookup();
+            index
+        }
+        _ => InvertedIndex {
+            version: INDEX_VERSION,
+            ..Default::default()
+        },
+    }
+}
+
+fn save_index(app: &AppHandle, index: &RwLock<InvertedIndex>) -> Result<(), String> {
+    let path = get_index_path(app)?;
+    let contents = serde_json::to_vec(&*index.read().unwrap()).map_err(|e| e.to_string())?;
+    atomic_file::write_atomic(&path, &contents).map_err(|e| e.to_string())
+}
+
+// 目录下的所有文件，跳过隐藏目录 (不使用 .gitignore 等规则，与文件监听保持一致)
+pub(crate) fn files_under(dir: &Path) -> Vec<PathBuf> {
+    WalkBuilder::new(dir)
+        .standard_filters(false)
+        .follow_links(false)
+        .filter_entry(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+// 与磁盘上的文件对齐：索引新增或修改的文件，移除已不存在的文件
+fn sync_with_disk(index: &RwLock<InvertedIndex>, data_dir: &Path) {
+    // 先清理不应被索引的文档，不必等到遍历完整个目录
+    index.write().unwrap().remove_excluded(data_dir);
+
+    let mut seen = HashSet::new();
+    for path in files_under(data_dir) {
+        if is_indexable(data_dir, &path) {
+            update_path(index, data_dir, &path);
+            seen.insert(path.to_string_lossy().to_string());
+        }
+    }
+
+    let stale: Vec<String> = index
+        .read()
+        .unwrap()
+        .by_path
+        .keys()
+        .filter(|p| !seen.contains(*p))
+        .cloned()
+        .collect();
+    let mut index = index.write().unwrap();
+    for path in stale {
+        index.remove(&path);
+    }
+}
+
+// 监听数据目录，变化的路径交给后台任务批量更新
+fn start_watcher(app: &AppHandle, data_dir: &Path) -> Result<(), String> {
+    let state = app.state::<SearchIndexState>();
+    let pending = state.pending.clone();
+    let root = data_dir.to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            // 忽略索引文件自身的写入等内部目录的变化
+            let paths = event.paths.into_iter().filter(|p| !is_excluded_dir(&root, p));
+            pending.lock().unwrap().extend(paths);
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(data_dir, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+    *state.watcher.lock().unwrap() = Some(watcher);
+    Ok(())
+}
+
+/// 加载全文索引和语义索引并与磁盘同步，然后监听数据目录的变化
+pub fn init_search_index(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let data_dir = match get_data_dir(&app) {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("[SearchIndex] Failed to resolve data dir: {}", e);
+                return;
+            }
+        };
+        let state = app.state::<SearchIndexState>();
+        let index = state.index.clone();
+        let pending = state.pending.clone();
+        let semantic = app.state::<SemanticIndexState>().index.clone();
+
+        // 先开始监听，避免同步期间的修改被遗漏
+        if let Err(e) = start_watcher(&app, &data_dir) {
+            eprintln!("[SearchIndex] Failed to watch data dir: {}", e);
+        }
+
+        let (task_app, task_index, task_dir) = (app.clone(), index.clone(), data_dir.clone());
+        let task_semantic = semantic.clone();
+        let synced = tauri::async_runtime::spawn_blocking(move || {
+            *task_index.write().unwrap() = load_index(&task_app);
+            sync_with_disk(&task_index, &task_dir);
+            *task_semantic.write().unwrap() = search_semantic::load_index(&task_app);
+            search_semantic::sync_with_disk(&task_semantic, &task_dir);
+            save_index(&task_app, &task_index)?;
+            search_semantic::save_index(&task_app, &task_semantic)
+        })
+        .await;
+        if let Ok(Err(e)) | Err(e) = synced.map_err(|e| e.to_string()) {
+            eprintln!("[SearchIndex] Failed to build index: {}", e);
+        }
+        state.ready.store(true, Ordering::SeqCst);
+
+        let mut dirty = false;
+        let mut last_save = Instant::now();
+        loop {
+            tokio::time::sleep(UPDATE_DELAY).await;
+            let paths: Vec<PathBuf> = pending.lock().unwrap().drain().collect();
+            let save_due = dirty && last_save.elapsed() >= SAVE_INTERVAL;
+            if paths.is_empty() && !save_due {
+                continue;
+            }
+
+            let (task_app, task_index, task_dir) = (app.clone(), index.clone(), data_dir.clone());
+            let task_semantic = semantic.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                let changed = paths.iter().fold(false, |changed, path| {
+                    let text_changed = update_path(&task_index, &task_dir, path);
+                    search_semantic::update_path(&task_semantic, &task_dir, path) | text_changed | changed
+                });
+                let saved = if (dirty || changed) && last_save.elapsed() >= SAVE_INTERVAL {
+                    Some(
+                        save_index(&task_app, &task_index)
+                            .and_then(|_| search_semantic::save_index(&task_app, &task_semantic)),
+                    )
+                } else {
+                    None
+                };
+                (changed, saved)
+            })
+            .await;
+
+            match result {
+                Ok((changed, None)) => dirty |= changed,
+                Ok((_, Some(saved))) => {
+                    if let Err(e) = saved {
+                        eprintln!("[SearchIndex] Failed to save index: {}", e);
+                    }
+                    dirty = false;
+                    last_save = Instant::now();
+                }
+                Err(e) => eprintln!("[SearchIndex] Update task failed: {}", e),
+            }
+        }
+    });
+}
+
+// 找到第一处命中所在的行作为摘要
+fn find_snippet(path: &Path, clauses: &[Clause]) -> Option<(usize, String)> {
+    let text = read_document(path)?;
+    let needles: Vec<&str> = clauses.iter().map(|c| c.raw()).collect();
+    text.lines().enumerate().find_map(|(i, line)| {
+        let lower = line.to_lowercase();
+        needles
+            .iter()
+            .any(|n| lower.contains(n))
+            .then(|| (i + 1, line.trim().chars().take(200).collect()))
+    })
+}
+
+// --- Tauri Commands ---
+
+/// 在全文索引中查询，结果按相关度排序
+/// 语法：空格分隔的词需全部命中，"..." 为短语，foo* 为前缀
+#[tauri::command]
+pub async fn search_index_query(
+    state: State<'_, SearchIndexState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<IndexQueryResponse, String> {
+    let index = state.index.clone();
+    let ready = state.ready.load(Ordering::SeqCst);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let clauses = parse_query(&query);
+        let (paths, total) = {
+            let index = index.read().unwrap();
+            let ranked = index.query(&clauses);
+            let total = ranked.len();
+            let paths: Vec<(String, f64)> = ranked
+                .into_iter()
+                .take(limit)
+                .filter_map(|(id, score)| index.docs.get(&id).map(|d| (d.path.clone(), score)))
+                .collect();
+            (paths, total)
+        };
+
+        let hits = paths
+            .into_iter()
+            .map(|(path, score)| {
+                let snippet = find_snippet(Path::new(&path), &clauses);
+                IndexHit {
+                    path,
+                    score,
+                    line_number: snippet.as_ref().map(|(n, _)| *n),
+                    snippet: snippet.map(|(_, s)| s),
+                }
+            })
+            .collect();
+        IndexQueryResponse { hits, total, ready }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn search_index_status(state: State<'_, SearchIndexState>) -> IndexStatus {
+    let index = state.index.read().unwrap();
+    IndexStatus {
+        ready: state.ready.load(Ordering::SeqCst),
+        documents: index.docs.len(),
+        terms: index.postings.len(),
+    }
+}
+
+/// 丢弃现有的全文索引和语义索引，重新扫描整个数据目录
+#[tauri::command]
+pub async fn search_index_rebuild(
+    app: AppHandle,
+    state: State<'_, SearchIndexState>,
+    semantic: State<'_, SemanticIndexState>,
+) -> Result<IndexStatus, String> {
+    let index = state.index.clone();
+    let semantic = semantic.index.clone();
+    let data_dir = get_data_dir(&app)?;
+
+    let task_app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut rebuilt = RwLock::new(InvertedIndex {
+            version: INDEX_VERSION,
+            ..Default::default()
+        });
+        sync_with_disk(&rebuilt, &data_dir);
+        std::mem::swap(&mut *index.write().unwrap(), rebuilt.get_mut().unwrap());
+        search_semantic::rebuild(&semantic, &data_dir);
+        save_index(&task_app, &index)?;
+        search_semantic::save_index(&task_app, &semantic)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    Ok(search_index_status(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_with(docs: &[(&str, &str)]) -> InvertedIndex {
+        let mut index = InvertedIndex {
+            version: INDEX_VERSION,
+            ..Default::default()
+        };
+        for (path, text) in docs {
+            index.insert(path.to_string(), 0, 0, text);
+        }
+        index
+    }
+
+    fn hits(index: &InvertedIndex, query: &str) -> Vec<String> {
+        index
+            .query(&parse_query(query))
+            .into_iter()
+            .map(|(id, _)| index.docs[&id].path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_words_and_cjk_bigrams() {
+        assert_eq!(tokenize("Hello, World_2"), vec!["hello", "world", "2"]);
+        assert_eq!(tokenize("宝剑锋"), vec!["宝剑", "剑锋"]);
+        assert_eq!(tokenize("剑 sword"), vec!["剑", "sword"]);
+        assert_eq!(tokenize("abc宝剑"), vec!["abc", "宝剑"]);
+    }
+
+    #[test]
+    fn single_cjk_character_matches_inside_words() {
+        let index = index_with(&[
+            ("a", "宝剑锋从磨砺出"),
+            ("b", "剑"),
+            ("c", "梅花香自苦寒来"),
+        ]);
+        let mut found = hits(&index, "剑");
+        found.sort();
+        assert_eq!(found, vec!["a", "b"]);
+        assert_eq!(hits(&index, "出"), vec!["a"]);
+        assert_eq!(hits(&index, "宝剑"), vec!["a"]);
+        // 单字不会让短语查询跨越不相邻的字
+        assert!(hits(&index, "宝锋").is_empty());
+    }
+
+    #[test]
+    fn repeated_characters_are_counted() {
+        let index = index_with(&[("a", "哈哈哈"), ("b", "哈，好的")]);
+        assert_eq!(hits(&index, "哈"), vec!["a", "b"]);
+        let id = index.by_path["a"];
+        let freqs = index.clause_frequencies(&parse_query("哈")[0]);
+        assert_eq!(freqs[&id], 3);
+    }
+
+    #[test]
+    fn bm25_requires_all_clauses_and_ranks_by_frequency() {
+        let index = index_with(&[
+            ("a", "dragon sword dragon"),
+            ("b", "dragon shield"),
+            ("c", "sword"),
+        ]);
+        assert_eq!(hits(&index, "dragon"), vec!["a", "b"]);
+        assert_eq!(hits(&index, "dragon sword"), vec!["a"]);
+        assert_eq!(hits(&index, "\"sword dragon\""), vec!["a"]);
+        assert!(hits(&index, "\"shield dragon\"").is_empty());
+        let mut prefix = hits(&index, "sh*");
+        prefix.sort();
+        assert_eq!(prefix, vec!["b"]);
+    }
+
+    #[test]
+    fn removing_documents_cleans_postings() {
+        let mut index = index_with(&[("a", "dragon"), ("b", "dragon sword")]);
+        assert!(index.remove("b"));
+        assert!(!index.postings.contains_key("sword"));
+        assert_eq!(index.total_length, 1);
+        assert_eq!(hits(&index, "dragon"), vec!["a"]);
+    }
+
+    #[test]
+    fn secret_files_are_never_indexed() {
+        let data_dir = Path::new("/data");
+        for file in [
+            "secrets.json",
+            "secret_profiles.json",
+            "secret_profiles/ab12.json",
+            "secrets_audit.jsonl",
+        ] {
+            assert!(!is_indexable(data_dir, &data_dir.join(file)), "{}", file);
+        }
+        assert!(is_indexable(data_dir, &data_dir.join("chats/a.json")));
+        assert!(!is_indexable(data_dir, &data_dir.join(".git/config.json")));
+    }
+
+    #[test]
+    fn previously_indexed_secret_files_are_removed() {
+        let data_dir = Path::new("/data");
+        let path = |file: &str| data_dir.join(file).to_string_lossy().to_string();
+        let (secrets, profile, notes) = (
+            path("secrets.json"),
+            path("secret_profiles/ab12.json"),
+            path("notes.md"),
+        );
+        let mut index = index_with(&[
+            (&secrets, "sk-secret"),
+            (&profile, "sk-other"),
+            (&notes, "notes"),
+        ]);
+
+        assert!(index.remove_excluded(data_dir));
+        assert_eq!(index.by_path.keys().collect::<Vec<_>>(), vec![&notes]);
+        assert!(hits(&index, "sk").is_empty());
+        assert!(!index.remove_excluded(data_dir));
+    }
+}
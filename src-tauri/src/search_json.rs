@@ -0,0 +1,394 @@
+// src-tauri/src/search_json.rs
+
+use crate::search::{self, SubMatch};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+// JSON 路径中的一段
+// 语法：以 '.' 分隔的字段名，[n] 为数组下标，[*] 或 * 为任意子节点，** 为任意深度
+// 例如 "entries[*].content"、"messages[*].content"、"**.description"
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Any,
+    Descend,
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonPath(Vec<Segment>);
+
+// 字符串值中的一处匹配
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonMatch {
+    pointer: String, // RFC 6901 JSON pointer，例如 "/entries/3/content"
+    value: String,
+    submatches: Vec<SubMatch>,
+}
+
+impl JsonMatch {
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid JSON path: {}", path);
+        let mut segments = Vec::new();
+
+        for part in path.split('.') {
+            if part.is_empty() {
+                return Err(invalid());
+            }
+            // 字段名部分，其后可跟任意个 [..]
+            let (name, mut rest) = match part.find('[') {
+                Some(i) => (&part[..i], &part[i..]),
+                None => (part, ""),
+            };
+            match name {
+                "" => {}
+                "*" => segments.push(Segment::Any),
+                "**" => segments.push(Segment::Descend),
+                _ => segments.push(Segment::Key(name.to_string())),
+            }
+            while !rest.is_empty() {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                if !rest.starts_with('[') {
+                    return Err(invalid());
+                }
+                let inner = &rest[1..end];
+                segments.push(match inner {
+                    "*" => Segment::Any,
+                    _ => Segment::Index(inner.parse().map_err(|_| invalid())?),
+                });
+                rest = &rest[end + 1..];
+            }
+        }
+        // 末尾的 ** 不再限定范围 (路径匹配完后本来就搜索整棵子树)，保留会重复收集同一个值
+        while segments.last() == Some(&Segment::Descend) {
+            segments.pop();
+        }
+
+        Ok(JsonPath(segments))
+    }
+}
+
+// 按 RFC 6901 转义指针中的一段
+fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+// 内嵌的图片等 base64 数据没有搜索意义
+fn is_data_url(value: &str) -> bool {
+    value.starts_with("data:")
+        && value
+            .split(',')
+            .next()
+            .map(|header| header.ends_with(";base64"))
+            .unwrap_or(false)
+}
+
+struct Searcher<'a> {
+    re: &'a Regex,
+    max_matches: Option<usize>,
+    matches: Vec<JsonMatch>,
+    visited: HashSet<(String, usize)>, // (指针, 路径位置)，避免 ** 导致重复遍历
+    truncated: bool,
+}
+
+impl Searcher<'_> {
+    // 路径已经匹配完，搜索子树中的所有字符串值
+    fn collect_strings(&mut self, value: &Value, pointer: &mut String) {
+        if self.truncated {
+            return;
+        }
+        match value {
+            Value::String(s) => {
+                if is_data_url(s) {
+                    return;
+                }
+                let submatches = search::find_submatches(self.re, s);
+                if submatches.is_empty() {
+                    return;
+                }
+                // 路径匹配到的节点可能互相嵌套 (例如 "**.a" 匹配 /a 和 /a/a)，同一个值只记录一次
+                if !self.visited.insert((pointer.clone(), usize::MAX)) {
+                    return;
+                }
+                if self.max_matches.map(|max| self.matches.len() >= max).unwrap_or(false) {
+                    self.truncated = true;
+                } else {
+                    self.matches.push(JsonMatch {
+                        pointer: pointer.clone(),
+                        value: s.clone(),
+                        submatches,
+                    });
+                }
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let len = pointer.len();
+                    pointer.push_str(&format!("/{}", i));
+                    self.collect_strings(item, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            Value::Object(map) => {
+                for (key, item) in map {
+                    let len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&escape_pointer(key));
+                    self.collect_strings(item, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn walk(&mut self, value: &Value, segments: &[Segment], depth: usize, pointer: &mut String) {
+        if self.truncated {
+            return;
+        }
+        if depth == segments.len() {
+            // 不同的 ** 展开可能到达同一节点
+            if self.visited.insert((pointer.clone(), depth)) {
+                self.collect_strings(value, pointer);
+            }
+            return;
+        }
+
+        match &segments[depth] {
+            Segment::Key(key) => {
+                if let Some(child) = value.get(key) {
+                    let len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&escape_pointer(key));
+                    self.walk(child, segments, depth + 1, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            Segment::Index(index) => {
+                if let Some(child) = value.get(*index) {
+                    let len = pointer.len();
+                    pointer.push_str(&format!("/{}", index));
+                    self.walk(child, segments, depth + 1, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            Segment::Any => self.walk_children(value, segments, depth + 1, pointer),
+            Segment::Descend => {
+                if !self.visited.insert((pointer.clone(), depth)) {
+                    return;
+                }
+                // ** 匹配零层或多层
+                self.walk(value, segments, depth + 1, pointer);
+                self.walk_children(value, segments, depth, pointer);
+            }
+        }
+    }
+
+    fn walk_children(&mut self, value: &Value, segments: &[Segment], depth: usize, pointer: &mut String) {
+        match value {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let len = pointer.len();
+                    pointer.push_str(&format!("/{}", i));
+                    self.walk(item, segments, depth, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            // 对象的 [*] 同样匹配所有字段，例如以 uid 为键的 lorebook entries
+            Value::Object(map) => {
+                for (key, item) in map {
+                    let len = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&escape_pointer(key));
+                    self.walk(item, segments, depth, pointer);
+                    pointer.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 在 JSON 文档的字符串值中搜索 (不匹配键名)，可以用 path 限定范围
+/// 返回的 bool 表示是否因 max_matches 截断
+pub fn search_json(
+    text: &str,
+    re: &Regex,
+    path: Option<&JsonPath>,
+    max_matches: Option<usize>,
+) -> Result<(Vec<JsonMatch>, bool), String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let mut searcher = Searcher {
+        re,
+        max_matches,
+        matches: Vec::new(),
+        visited: HashSet::new(),
+        truncated: false,
+    };
+    let segments = path.map(|p| p.0.as_slice()).unwrap_or(&[]);
+    searcher.walk(&root, segments, 0, &mut String::new());
+
+    Ok((searcher.matches, searcher.truncated))
+}
+
+/// 提取 JSON 文档中所有字符串值 (不含键名和 base64 数据)，以换行连接
+pub fn extract_text(text: &str) -> Result<String, String> {
+    fn collect<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::String(s) if !is_data_url(s) => out.push(s),
+            Value::Array(items) => items.iter().for_each(|item| collect(item, out)),
+            Value::Object(map) => map.values().for_each(|item| collect(item, out)),
+            _ => {}
+        }
+    }
+
+    let root: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let mut values = Vec::new();
+    collect(&root, &mut values);
+    Ok(values.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(text: &str, pattern: &str, path: Option<&str>) -> Vec<String> {
+        let re = Regex::new(pattern).unwrap();
+        let path = path.map(|p| JsonPath::parse(p).unwrap());
+        let (matches, _) = search_json(text, &re, path.as_ref(), None).unwrap();
+        let mut pointers: Vec<String> = matches.iter().map(|m| m.pointer().to_string()).collect();
+        pointers.sort();
+        pointers
+    }
+
+    #[test]
+    fn parses_path_segments() {
+        let path = JsonPath::parse("entries[*].content").unwrap();
+        assert_eq!(
+            path.0,
+            vec![
+                Segment::Key("entries".into()),
+                Segment::Any,
+                Segment::Key("content".into())
+            ]
+        );
+
+        assert_eq!(
+            JsonPath::parse("a.**").unwrap().0,
+            vec![Segment::Key("a".into())]
+        );
+
+        let path = JsonPath::parse("**.list[2][*].*").unwrap();
+        assert_eq!(
+            path.0,
+            vec![
+                Segment::Descend,
+                Segment::Key("list".into()),
+                Segment::Index(2),
+                Segment::Any,
+                Segment::Any
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        for path in ["", "a..b", "a.", "a[1", "a[x]", "a[1]b", "a[-1]"] {
+            assert!(JsonPath::parse(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn path_limits_the_searched_values() {
+        let doc = json!({
+            "name": "sword",
+            "entries": [{ "content": "a sword", "comment": "sword" }, { "content": "shield" }],
+        })
+        .to_string();
+        assert_eq!(
+            pointers(&doc, "sword", Some("entries[*].content")),
+            vec!["/entries/0/content"]
+        );
+        assert_eq!(
+            pointers(&doc, "sword", Some("entries[0]")),
+            vec!["/entries/0/comment", "/entries/0/content"]
+        );
+        assert_eq!(pointers(&doc, "sword", None).len(), 3);
+    }
+
+    #[test]
+    fn any_segment_matches_object_fields() {
+        let doc =
+            json!({ "entries": { "12": { "content": "sword" }, "7": { "content": "sword" } } })
+                .to_string();
+        assert_eq!(
+            pointers(&doc, "sword", Some("entries.*.content")),
+            vec!["/entries/12/content", "/entries/7/content"]
+        );
+    }
+
+    #[test]
+    fn descend_visits_each_value_once() {
+        let doc =
+            json!({ "a": { "description": "sword", "b": { "description": "sword" } } }).to_string();
+        assert_eq!(
+            pointers(&doc, "sword", Some("**.description")),
+            vec!["/a/b/description", "/a/description"]
+        );
+        // 以 ** 结尾时，每个值也只返回一次
+        assert_eq!(pointers(&doc, "sword", Some("**")).len(), 2);
+        assert_eq!(pointers(&doc, "sword", Some("a.**")).len(), 2);
+
+        let nested = json!({ "a": { "a": "sword" } }).to_string();
+        assert_eq!(pointers(&nested, "sword", Some("**.a")), vec!["/a/a"]);
+    }
+
+    #[test]
+    fn keys_and_data_urls_are_not_matched() {
+        let doc = json!({
+            "sword": 1,
+            "a/b~c": "sword",
+            "image": "data:image/png;base64,c3dvcmQ=",
+            "link": "data:text/plain,sword",
+        })
+        .to_string();
+        assert_eq!(
+            pointers(&doc, "sword|c3dvcmQ", None),
+            vec!["/a~1b~0c", "/link"]
+        );
+    }
+
+    #[test]
+    fn max_matches_truncates() {
+        let doc = json!(["sword", "sword", "sword"]).to_string();
+        let re = Regex::new("sword").unwrap();
+        let (matches, truncated) = search_json(&doc, &re, None, Some(2)).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert!(truncated);
+
+        let (matches, truncated) = search_json(&doc, &re, None, Some(3)).unwrap();
+        assert_eq!(matches.len(), 3);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn extract_text_skips_keys_and_data_urls() {
+        let doc =
+            json!({ "title": "sword", "image": "data:image/png;base64,AAAA", "n": 1 }).to_string();
+        assert_eq!(extract_text(&doc).unwrap(), "sword");
+        assert!(extract_text("{").is_err());
+    }
+}
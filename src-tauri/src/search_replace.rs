@@ -0,0 +1,605 @@
+// src-tauri/src/search_replace.rs
+
+use crate::atomic_file;
+use crate::search::{self, SearchConfig, UnreadableFile};
+use crate::search_index;
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, Rng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+// 最多保留的撤销记录数
+const MAX_UNDO_RECORDS: usize = 20;
+
+// 单处替换的预览
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplaceEdit {
+    id: usize, // 文件内的序号，apply_replace 时用于选择
+    line_number: usize,
+    start: usize, // 在整个文件中的字节偏移
+    end: usize,
+    original: String,
+    replacement: String,
+    line_before: String, // 替换前的整行
+    line_after: String,  // 仅应用这一处替换后的整行
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReplacePreview {
+    path: String,
+    sha256: String, // 预览时的文件内容哈希，应用时用于检测文件是否已被修改
+    edits: Vec<ReplaceEdit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacePreview {
+    files: Vec<FileReplacePreview>,
+    // 无法替换的文件 (例如非 UTF-8 编码、超过大小上限)，不包括二进制文件
+    unreadable: Vec<UnreadableFile>,
+}
+
+// 选择要应用的替换，edit_ids 为空表示全部应用
+#[derive(Debug, Deserialize)]
+pub struct ReplaceSelection {
+    path: String,
+    sha256: String,
+    #[serde(default)]
+    edit_ids: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplaceResult {
+    undo_id: String,
+    files_changed: usize,
+    replacements: usize,
+}
+
+// 撤销记录：保存修改前的内容和修改后的哈希
+#[derive(Debug, Serialize, Deserialize)]
+struct UndoFile {
+    path: String,
+    original: String,
+    replaced_sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoRecord {
+    id: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    created_at: chrono::DateTime<Utc>,
+    keyword: String,
+    replacement: String,
+    files: Vec<UndoFile>,
+}
+
+// 列表中显示的撤销记录摘要
+#[derive(Debug, Serialize)]
+pub struct UndoSummary {
+    id: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    created_at: chrono::DateTime<Utc>,
+    keyword: String,
+    replacement: String,
+    paths: Vec<String>,
+}
+
+// --- 辅助函数 ---
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn get_undo_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let dir = app_data_dir.join("replace_undo");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+// 计算替换文本：正则模式下支持 $1 / ${name} 捕获组，普通模式按字面替换
+fn expand_replacement(re: &Regex, text: &str, start: usize, replacement: &str, is_regex: bool) -> String {
+    if !is_regex {
+        return replacement.to_string();
+    }
+    match re.captures_at(text, start) {
+        Some(caps) => {
+            let mut out = String::new();
+            caps.expand(replacement, &mut out);
+            out
+        }
+        None => replacement.to_string(),
+    }
+}
+
+// 逐行计算文件中的所有替换 (与 search_in_files 一样按行匹配)
+fn compute_edits(content: &str, re: &Regex, replacement: &str, is_regex: bool) -> Vec<ReplaceEdit> {
+    let mut edits = Vec::new();
+    let mut offset = 0;
+
+    for (index, raw_line) in content.split_inclusive('\n').enumerate() {
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+        for m in re.find_iter(line) {
+            let replaced = expand_replacement(re, line, m.start(), replacement, is_regex);
+            let line_after = format!("{}{}{}", &line[..m.start()], replaced, &line[m.end()..]);
+            edits.push(ReplaceEdit {
+                id: edits.len(),
+                line_number: index + 1,
+                start: offset + m.start(),
+                end: offset + m.end(),
+                original: m.as_str().to_string(),
+                replacement: replaced,
+                line_before: line.to_string(),
+                line_after,
+            });
+        }
+        offset += raw_line.len();
+    }
+
+    edits
+}
+
+// 按字节偏移依次应用选中的替换
+fn apply_edits(content: &str, edits: &[&ReplaceEdit]) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for edit in edits {
+        out.push_str(&content[cursor..edit.start]);
+        out.push_str(&edit.replacement);
+        cursor = edit.end;
+    }
+    out.push_str(&content[cursor..]);
+    out
+}
+
+// 同一文件 (按规范化路径) 的多个选择，合并后的结果
+#[derive(Debug)]
+struct MergedSelection {
+    path: PathBuf,
+    display: String,
+    sha256: String,
+    edit_ids: Option<HashSet<usize>>, // None 表示全部应用
+}
+
+// 将指向同一文件的选择合并为一个，避免重复替换或对同一文件多次写入
+// 同一文件的预览哈希不一致时拒绝，说明两次预览之间文件被修改过
+fn merge_selections(selections: Vec<(PathBuf, &ReplaceSelection)>) -> Result<Vec<MergedSelection>, String> {
+    let mut merged: Vec<MergedSelection> = Vec::new();
+    for (path, selection) in selections {
+        let ids = selection
+            .edit_ids
+            .as_ref()
+            .map(|ids| ids.iter().copied().collect::<HashSet<usize>>());
+        match merged.iter_mut().find(|m| m.path == path) {
+            Some(existing) => {
+                if existing.sha256 != selection.sha256 {
+                    return Err(format!(
+                        "File changed since preview, please preview again: {}",
+                        selection.path
+                    ));
+                }
+                existing.edit_ids = match (existing.edit_ids.take(), ids) {
+                    (Some(mut a), Some(b)) => {
+                        a.extend(b);
+                        Some(a)
+                    }
+                    _ => None,
+                };
+            }
+            None => merged.push(MergedSelection {
+                path,
+                display: selection.path.clone(),
+                sha256: selection.sha256.clone(),
+                edit_ids: ids,
+            }),
+        }
+    }
+    Ok(merged)
+}
+
+// 读取可替换的文本文件，二进制文件返回 Ok(None)
+// 与搜索使用相同的二进制判断和大小上限；写回时无法保持 UTF-8 以外的编码，这些文件返回 Err
+fn read_text(path: &Path, max_size: u64) -> Result<Option<String>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let Some(bytes) = search::read_bytes(file, max_size)? else {
+        return Ok(None);
+    };
+    // 不带 BOM 的 UTF-16 文本也可能是合法的 UTF-8，按 NULL 字节区分
+    if !bytes.contains(&0) {
+        if let Ok(text) = std::str::from_utf8(&bytes) {
+            return Ok(Some(text.to_string()));
+        }
+    }
+    match search::decode_text(&bytes)? {
+        Some(_) => Err("Replacing in files that are not UTF-8 encoded is not supported".to_string()),
+        None => Ok(None),
+    }
+}
+
+fn delete_undo_record(app: &AppHandle, id: &str) -> Result<(), String> {
+    let path = get_undo_dir(app)?.join(format!("{}.json", id));
+    fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+/// 将多份新内容作为一个整体写入：先全部写入临时文件，再逐个 rename；
+/// rename 中途失败时，用原始内容恢复已经替换的文件
+fn commit_files(files: &[(PathBuf, String, String)]) -> Result<(), String> {
+    // (目标路径, 原始内容, 新内容)
+    let mut temps = Vec::with_capacity(files.len());
+    for (path, _, new_content) in files {
+        match atomic_file::write_temp(path, new_content.as_bytes()) {
+            Ok(tmp) => temps.push(tmp),
+            Err(e) => {
+                for tmp in &temps {
+                    let _ = fs::remove_file(tmp);
+                }
+                return Err(format!("Failed to write {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    for (i, ((path, _, _), tmp)) in files.iter().zip(&temps).enumerate() {
+        if let Err(e) = fs::rename(tmp, path) {
+            for tmp in &temps[i..] {
+                let _ = fs::remove_file(tmp);
+            }
+            for (done_path, original, _) in &files[..i] {
+                let _ = atomic_file::write_atomic(done_path, original.as_bytes());
+            }
+            return Err(format!("Failed to replace {}: {}", path.display(), e));
+        }
+    }
+
+    Ok(())
+}
+
+fn save_undo_record(app: &AppHandle, record: &UndoRecord) -> Result<(), String> {
+    let dir = get_undo_dir(app)?;
+    let contents = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    atomic_file::write_atomic(&dir.join(format!("{}.json", record.id)), contents.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    // 清理过旧的撤销记录
+    let mut records = read_undo_records(app)?;
+    if records.len() > MAX_UNDO_RECORDS {
+        records.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        for old in &records[MAX_UNDO_RECORDS..] {
+            let _ = fs::remove_file(dir.join(format!("{}.json", old.id)));
+        }
+    }
+    Ok(())
+}
+
+fn read_undo_records(app: &AppHandle) -> Result<Vec<UndoRecord>, String> {
+    let dir = get_undo_dir(app)?;
+    let mut records = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        if let Ok(record) = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_str::<UndoRecord>(&c).map_err(|e| e.to_string()))
+        {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+fn validate_undo_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid undo id: {}", id));
+    }
+    Ok(())
+}
+
+// --- Tauri Commands ---
+
+/// 预览替换结果，不修改任何文件
+#[tauri::command]
+pub async fn preview_replace(
+    app: AppHandle,
+    keyword: String,
+    replacement: String,
+    config: SearchConfig,
+) -> Result<ReplacePreview, String> {
+    if keyword.is_empty() {
+        return Ok(ReplacePreview {
+            files: vec![],
+            unreadable: vec![],
+        });
+    }
+    let re = search::build_regex(&keyword, &config)?;
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let walker = search::build_walker(&config, &data_dir)?;
+
+    let task = tauri::async_runtime::spawn_blocking(move || {
+        let max_size = config.max_file_size.unwrap_or(search::DEFAULT_MAX_FILE_SIZE);
+        let mut previews = Vec::new();
+        let mut unreadable = Vec::new();
+        for entry in walker.build().filter_map(|e| e.ok()) {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let content = match read_text(entry.path(), max_size) {
+                Ok(Some(c)) => c,
+                Ok(None) => continue,
+                Err(reason) => {
+                    let path = entry.path().to_string_lossy().to_string();
+                    unreadable.push(UnreadableFile::new(Some(path), reason));
+                    continue;
+                }
+            };
+            let edits = compute_edits(&content, &re, &replacement, config.is_regex);
+            if !edits.is_empty() {
+                previews.push(FileReplacePreview {
+                    path: entry.path().to_string_lossy().to_string(),
+                    sha256: sha256_hex(content.as_bytes()),
+                    edits,
+                });
+            }
+        }
+        previews.sort_by(|a, b| a.path.cmp(&b.path));
+        ReplacePreview {
+            files: previews,
+            unreadable,
+        }
+    });
+
+    task.await.map_err(|e| e.to_string())
+}
+
+/// 应用选中的替换，所有文件作为一个整体写入，并生成撤销记录
+/// 任何文件在预览之后被修改过，都会拒绝整个操作
+#[tauri::command]
+pub async fn apply_replace(
+    app: AppHandle,
+    keyword: String,
+    replacement: String,
+    config: SearchConfig,
+    selections: Vec<ReplaceSelection>,
+) -> Result<ReplaceResult, String> {
+    let re = search::build_regex(&keyword, &config)?;
+    let target_dir = PathBuf::from(&config.target_dir)
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let data_dir = data_dir.canonicalize().unwrap_or(data_dir);
+
+    let mut resolved = Vec::with_capacity(selections.len());
+    for selection in &selections {
+        let path = PathBuf::from(&selection.path);
+        // 只允许修改搜索目录内的文件
+        let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+        if !canonical.starts_with(&target_dir) {
+            return Err(format!("Path is outside of the search directory: {}", selection.path));
+        }
+        // 密钥文件和撤销记录等内部文件不允许替换
+        if search_index::is_excluded_dir(&data_dir, &canonical) {
+            return Err(format!("Cannot replace in app internal files: {}", selection.path));
+        }
+        resolved.push((canonical, selection));
+    }
+
+    let max_size = config.max_file_size.unwrap_or(search::DEFAULT_MAX_FILE_SIZE);
+    let mut files: Vec<(PathBuf, String, String)> = Vec::new();
+    let mut replacements = 0;
+
+    for selection in merge_selections(resolved)? {
+        let content = read_text(&selection.path, max_size)
+            .map_err(|reason| format!("{}: {}", selection.display, reason))?
+            .ok_or_else(|| format!("File is not a readable text file: {}", selection.display))?;
+        if sha256_hex(content.as_bytes()) != selection.sha256 {
+            return Err(format!(
+                "File changed since preview, please preview again: {}",
+                selection.display
+            ));
+        }
+
+        let edits = compute_edits(&content, &re, &replacement, config.is_regex);
+        let selected: Vec<&ReplaceEdit> = match &selection.edit_ids {
+            Some(ids) => edits.iter().filter(|e| ids.contains(&e.id)).collect(),
+            None => edits.iter().collect(),
+        };
+        if selected.is_empty() {
+            continue;
+        }
+
+        replacements += selected.len();
+        let new_content = apply_edits(&content, &selected);
+        files.push((selection.path, content, new_content));
+    }
+
+    let undo = UndoRecord {
+        id: rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect(),
+        created_at: Utc::now(),
+        keyword,
+        replacement,
+        files: files
+            .iter()
+            .map(|(path, original, new_content)| UndoFile {
+                path: path.to_string_lossy().to_string(),
+                original: original.clone(),
+                replaced_sha256: sha256_hex(new_content.as_bytes()),
+            })
+            .collect(),
+    };
+
+    // 先保存撤销记录，保证写入文件后一定可以撤销；写入失败时文件已恢复原样，撤销记录随之删除
+    if !files.is_empty() {
+        save_undo_record(&app, &undo)?;
+        if let Err(e) = commit_files(&files) {
+            let _ = delete_undo_record(&app, &undo.id);
+            return Err(e);
+        }
+    }
+
+    Ok(ReplaceResult {
+        undo_id: undo.id,
+        files_changed: files.len(),
+        replacements,
+    })
+}
+
+#[tauri::command]
+pub fn list_replace_undo(app: AppHandle) -> Result<Vec<UndoSummary>, String> {
+    let mut records = read_undo_records(&app)?;
+    records.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(records
+        .into_iter()
+        .map(|r| UndoSummary {
+            id: r.id,
+            created_at: r.created_at,
+            keyword: r.keyword,
+            replacement: r.replacement,
+            paths: r.files.into_iter().map(|f| f.path).collect(),
+        })
+        .collect())
+}
+
+/// 撤销一次替换
+/// 文件在替换后又被修改过时默认拒绝，force 为 true 时强制恢复
+#[tauri::command]
+pub fn undo_replace(app: AppHandle, undo_id: String, force: Option<bool>) -> Result<usize, String> {
+    validate_undo_id(&undo_id)?;
+    let record_path = get_undo_dir(&app)?.join(format!("{}.json", undo_id));
+    let contents = fs::read_to_string(&record_path)
+        .map_err(|_| format!("Undo record not found: {}", undo_id))?;
+    let record: UndoRecord = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+
+    let mut files = Vec::with_capacity(record.files.len());
+    for file in &record.files {
+        let path = PathBuf::from(&file.path);
+        let current = fs::read_to_string(&path).unwrap_or_default();
+        if !force.unwrap_or(false) && sha256_hex(current.as_bytes()) != file.replaced_sha256 {
+            return Err(format!("File changed after replace: {}", file.path));
+        }
+        files.push((path, current, file.original.clone()));
+    }
+
+    commit_files(&files)?;
+    fs::remove_file(record_path).map_err(|e| e.to_string())?;
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(path: &str, sha256: &str, edit_ids: Option<Vec<usize>>) -> ReplaceSelection {
+        ReplaceSelection {
+            path: path.into(),
+            sha256: sha256.into(),
+            edit_ids,
+        }
+    }
+
+    #[test]
+    fn selections_for_the_same_file_are_merged() {
+        let a = selection("a.txt", "h1", Some(vec![0, 2]));
+        let a_again = selection("./a.txt", "h1", Some(vec![2, 3]));
+        let b = selection("b.txt", "h2", Some(vec![1]));
+        let merged = merge_selections(vec![
+            (PathBuf::from("/w/a.txt"), &a),
+            (PathBuf::from("/w/b.txt"), &b),
+            (PathBuf::from("/w/a.txt"), &a_again),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].path, PathBuf::from("/w/a.txt"));
+        assert_eq!(merged[0].edit_ids, Some(HashSet::from([0, 2, 3])));
+        assert_eq!(merged[1].edit_ids, Some(HashSet::from([1])));
+    }
+
+    #[test]
+    fn selecting_all_wins_over_specific_edits() {
+        let some = selection("a.txt", "h1", Some(vec![1]));
+        let all = selection("a.txt", "h1", None);
+        let merged = merge_selections(vec![
+            (PathBuf::from("/w/a.txt"), &some),
+            (PathBuf::from("/w/a.txt"), &all),
+        ])
+        .unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].edit_ids, None);
+    }
+
+    #[test]
+    fn conflicting_hashes_are_rejected() {
+        let old = selection("a.txt", "h1", None);
+        let new = selection("a.txt", "h2", None);
+        assert!(merge_selections(vec![
+            (PathBuf::from("/w/a.txt"), &old),
+            (PathBuf::from("/w/a.txt"), &new),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn duplicate_edit_ids_apply_once() {
+        let content = "foo foo\nfoo\n";
+        let re = Regex::new("foo").unwrap();
+        let edits = compute_edits(content, &re, "bar", false);
+        let ids: HashSet<usize> = [1, 1, 2].into_iter().collect();
+        let selected: Vec<&ReplaceEdit> = edits.iter().filter(|e| ids.contains(&e.id)).collect();
+        assert_eq!(apply_edits(content, &selected), "foo bar\nbar\n");
+    }
+
+    #[test]
+    fn only_utf8_text_files_are_replaceable() {
+        let dir = std::env::temp_dir().join(format!("replace-read-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+
+        let utf8 = write("utf8.txt", "宝剑 sword".as_bytes());
+        assert_eq!(
+            read_text(&utf8, 1024).unwrap().as_deref(),
+            Some("宝剑 sword")
+        );
+        // BOM 作为内容的一部分保留，写回时不会丢失
+        let bom = write("bom.txt", "\u{feff}sword".as_bytes());
+        assert_eq!(
+            read_text(&bom, 1024).unwrap().as_deref(),
+            Some("\u{feff}sword")
+        );
+
+        // 搜索能找到匹配的其他编码文件会被报告，而不是被静默跳过
+        let (gbk, _, _) =
+            encoding_rs::GBK.encode("这是一段用国标码保存的中文文本，用来检查编码识别是否正确。");
+        assert!(read_text(&write("gbk.txt", &gbk), 1024).is_err());
+        let utf16: Vec<u8> = "sword"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert!(read_text(&write("utf16.txt", &utf16), 1024).is_err());
+
+        assert_eq!(
+            read_text(
+                &write(
+                    "binary.bin",
+                    &[0x7F, b'E', b'L', b'F', 0, 1, 0, 0, 2, 0, 0, 0]
+                ),
+                1024
+            )
+            .unwrap(),
+            None
+        );
+        assert!(read_text(&utf8, 4).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
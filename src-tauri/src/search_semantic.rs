@@ -0,0 +1,646 @@
+// src-tauri/src/search_semantic.rs
+
+use crate::atomic_file;
+use crate::search;
+use crate::search_index;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tauri::{AppHandle, State};
+
+// 索引格式或特征提取方式变化时递增，旧索引会被丢弃并重建
+const SEMANTIC_VERSION: u32 = 1;
+const SEMANTIC_FILE: &str = "semantic.json";
+// 特征哈希的桶数，足够大以减少冲突
+const FEATURE_BUCKETS: u32 = 1 << 20;
+const PREVIEW_CHARS: usize = 200;
+const DEFAULT_TOP_K: usize = 20;
+// 混合得分中 BM25 与余弦相似度的权重
+const BM25_WEIGHT: f64 = 0.4;
+const COSINE_WEIGHT: f64 = 0.6;
+// BM25 得分压缩到 [0, 1) 时的半饱和点
+const BM25_SATURATION: f64 = 5.0;
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitKind {
+    ChatMessage,
+    LorebookEntry,
+}
+
+// 一个检索单元：一条聊天消息 (的一个替代方案) 或一个世界书条目
+#[derive(Debug, Serialize, Deserialize)]
+struct Unit {
+    path: String,
+    pointer: String, // 在文件中的 JSON pointer
+    kind: UnitKind,
+    title: String,
+    role: Option<String>,
+    preview: String,
+    length: u32,
+    features: Vec<(u32, u32)>, // (特征桶, 词频)，按桶排序
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    modified: u64,
+    size: u64,
+    units: Vec<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SemanticIndex {
+    version: u32,
+    next_unit: u64,
+    units: HashMap<u64, Unit>,
+    files: HashMap<String, FileEntry>,
+    doc_freq: HashMap<u32, u32>, // 特征桶 -> 包含该特征的单元数
+    total_length: u64,
+}
+
+#[derive(Default)]
+pub struct SemanticIndexState {
+    pub(crate) index: Arc<RwLock<SemanticIndex>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SemanticHit {
+    path: String,
+    pointer: String,
+    kind: UnitKind,
+    title: String,
+    role: Option<String>,
+    preview: String,
+    score: f64,
+}
+
+// 从文件中提取出的单元
+struct ExtractedUnit {
+    pointer: String,
+    kind: UnitKind,
+    title: String,
+    role: Option<String>,
+    text: String,
+}
+
+// --- 特征提取 ---
+
+// FNV-1a，保证持久化的特征桶在不同版本之间稳定
+fn feature_bucket(token: &str) -> u32 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in token.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % FEATURE_BUCKETS as u64) as u32
+}
+
+// 简单的英文词干化，让 argue / argued / arguing 落在同一个特征上
+fn stem(token: &str) -> &str {
+    if !token.is_ascii() || token.len() <= 4 {
+        return token;
+    }
+    let stripped = ["ing", "ed", "es", "ly", "s"]
+        .iter()
+        .find_map(|suffix| token.strip_suffix(*suffix).filter(|rest| rest.len() >= 3))
+        .unwrap_or(token);
+    stripped.strip_suffix('e').filter(|rest| rest.len() >= 3).unwrap_or(stripped)
+}
+
+// 文本 -> 稀疏特征 (按桶排序) 与词数
+fn extract_features(text: &str) -> (Vec<(u32, u32)>, u32) {
+    let tokens = search_index::tokenize(text);
+    let mut counts: HashMap<u32, u32> = HashMap::new();
+    for token in &tokens {
+        *counts.entry(feature_bucket(stem(token))).or_insert(0) += 1;
+    }
+    let mut features: Vec<(u32, u32)> = counts.into_iter().collect();
+    features.sort_unstable();
+    (features, tokens.len() as u32)
+}
+
+fn preview(text: &str) -> String {
+    let text = text.trim();
+    let mut preview: String = text.chars().take(PREVIEW_CHARS).collect();
+    if preview.len() < text.len() {
+        preview.push('…');
+    }
+    preview
+}
+
+// --- 单元提取 ---
+
+// 聊天：messages[*].alternatives[*]，分支中的消息递归处理
+fn extract_messages(items: &[Value], base: &str, chat_name: &str, out: &mut Vec<ExtractedUnit>) {
+    for (i, item) in items.iter().enumerate() {
+        let role = item.get("role").and_then(|r| r.as_str()).map(str::to_string);
+        let alternatives = match item.get("alternatives").and_then(|a| a.as_array()) {
+            Some(a) => a,
+            None => continue,
+        };
+        for (j, alt) in alternatives.iter().enumerate() {
+            let pointer = format!("{}/{}/alternatives/{}", base, i, j);
+            match alt.get("type").and_then(|t| t.as_str()) {
+                Some("branch") => {
+                    if let Some(messages) = alt.get("messages").and_then(|m| m.as_array()) {
+                        extract_messages(messages, &format!("{}/messages", pointer), chat_name, out);
+                    }
+                }
+                _ => {
+                    let content = alt.get("content").and_then(|c| c.as_str()).unwrap_or("");
+                    if !content.trim().is_empty() {
+                        out.push(ExtractedUnit {
+                            pointer,
+                            kind: UnitKind::ChatMessage,
+                            title: chat_name.to_string(),
+                            role: role.clone(),
+                            text: content.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 识别聊天文件和世界书文件，其它 JSON 不产生单元
+fn extract_units(root: &Value) -> Vec<ExtractedUnit> {
+    let mut units = Vec::new();
+    let name = root.get("name").and_then(|n| n.as_str()).unwrap_or("");
+
+    if let Some(messages) = root.get("messages").and_then(|m| m.as_array()) {
+        extract_messages(messages, "/messages", name, &mut units);
+    }
+
+    if let Some(entries) = root.get("entries").and_then(|e| e.as_array()) {
+        for (i, entry) in entries.iter().enumerate() {
+            let content = match entry
+                .get("activationEffect")
+                .and_then(|effect| effect.get("content"))
+                .and_then(|c| c.as_str())
+            {
+                Some(content) => content,
+                None => continue,
+            };
+            let field = |key: &str| entry.get(key).and_then(|v| v.as_str()).unwrap_or("");
+            let text = [field("name"), field("description"), content]
+                .iter()
+                .filter(|s| !s.trim().is_empty())
+                .copied()
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text.is_empty() {
+                continue;
+            }
+            units.push(ExtractedUnit {
+                pointer: format!("/entries/{}", i),
+                kind: UnitKind::LorebookEntry,
+                title: field("name").to_string(),
+                role: None,
+                text,
+            });
+        }
+    }
+
+    units
+}
+
+// --- 索引 ---
+
+impl SemanticIndex {
+    fn empty() -> Self {
+        Self {
+            version: SEMANTIC_VERSION,
+            ..Default::default()
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> bool {
+        let entry = match self.files.remove(path) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        for id in entry.units {
+            if let Some(unit) = self.units.remove(&id) {
+                self.total_length -= unit.length as u64;
+                for (bucket, _) in unit.features {
+                    if let Some(df) = self.doc_freq.get_mut(&bucket) {
+                        *df -= 1;
+                        if *df == 0 {
+                            self.doc_freq.remove(&bucket);
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn insert_file(&mut self, path: String, modified: u64, size: u64, extracted: Vec<ExtractedUnit>) {
+        self.remove_file(&path);
+
+        let mut ids = Vec::with_capacity(extracted.len());
+        for unit in extracted {
+            let (features, length) = extract_features(&unit.text);
+            for (bucket, _) in &features {
+                *self.doc_freq.entry(*bucket).or_insert(0) += 1;
+            }
+            let id = self.next_unit;
+            self.next_unit += 1;
+            self.total_length += length as u64;
+            self.units.insert(
+                id,
+                Unit {
+                    path: path.clone(),
+                    pointer: unit.pointer,
+                    kind: unit.kind,
+                    title: unit.title,
+                    role: unit.role,
+                    preview: preview(&unit.text),
+                    length,
+                    features,
+                },
+            );
+            ids.push(id);
+        }
+        self.files.insert(path, FileEntry { modified, size, units: ids });
+    }
+
+    // 移除不应被索引的文件 (例如旧版本索引过的密钥文件)
+    fn remove_excluded(&mut self, data_dir: &Path) -> bool {
+        let paths: Vec<String> = self
+            .files
+            .keys()
+            .filter(|p| !search_index::is_indexable(data_dir, Path::new(p)))
+            .cloned()
+            .collect();
+        paths.iter().fold(false, |changed, p| self.remove_file(p) | changed)
+    }
+
+    fn idf(&self, bucket: u32) -> f64 {
+        let n = self.units.len() as f64;
+        let df = self.doc_freq.get(&bucket).copied().unwrap_or(0) as f64;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    // BM25 与 TF-IDF 余弦相似度的混合得分
+    fn query(&self, text: &str, filter: impl Fn(&Unit) -> bool) -> Vec<(u64, f64)> {
+        let (query, _) = extract_features(text);
+        if query.is_empty() || self.units.is_empty() {
+            return Vec::new();
+        }
+        let avg_length = (self.total_length as f64 / self.units.len() as f64).max(1.0);
+
+        let idf: HashMap<u32, f64> = query.iter().map(|(b, _)| (*b, self.idf(*b))).collect();
+        let query_weights: HashMap<u32, f64> = query
+            .iter()
+            .map(|(b, tf)| (*b, (1.0 + (*tf as f64).ln()) * idf[b]))
+            .collect();
+        let query_norm = query_weights.values().map(|w| w * w).sum::<f64>().sqrt();
+        if query_norm == 0.0 {
+            return Vec::new();
+        }
+
+        let mut scored = Vec::new();
+        for (id, unit) in &self.units {
+            if !filter(unit) {
+                continue;
+            }
+            let mut dot = 0.0;
+            let mut norm = 0.0;
+            let mut bm25 = 0.0;
+            for (bucket, tf) in &unit.features {
+                let tf = *tf as f64;
+                let weight = (1.0 + tf.ln()) * self.idf(*bucket);
+                norm += weight * weight;
+                if let Some(q) = query_weights.get(bucket) {
+                    dot += weight * q;
+                    bm25 += idf[bucket] * tf * (BM25_K1 + 1.0)
+                        / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * unit.length as f64 / avg_length));
+                }
+            }
+            if dot <= 0.0 {
+                continue;
+            }
+            let cosine = dot / (norm.sqrt() * query_norm);
+            let score = COSINE_WEIGHT * cosine + BM25_WEIGHT * bm25 / (bm25 + BM25_SATURATION);
+            scored.push((*id, score));
+        }
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored
+    }
+}
+
+// --- 与文件同步 ---
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or(false)
+}
+
+/// 更新单个路径，返回索引是否有变化；目录被删除时移除其下的所有文件
+pub(crate) fn update_path(index: &RwLock<SemanticIndex>, data_dir: &Path, path: &Path) -> bool {
+    let key = path.to_string_lossy().to_string();
+    match search_index::file_stamp(path) {
+        Some((modified, size)) if is_json(path) && search_index::is_indexable(data_dir, path) => {
+            let current = index
+                .read()
+                .unwrap()
+                .files
+                .get(&key)
+                .map(|f| f.modified == modified && f.size == size)
+                .unwrap_or(false);
+            if current {
+                return false;
+            }
+            let extracted = search::read_text_file(path)
+                .ok()
+                .flatten()
+                .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+                .map(|root| extract_units(&root))
+                .unwrap_or_default();
+            let mut index = index.write().unwrap();
+            if extracted.is_empty() {
+                return index.remove_file(&key);
+            }
+            index.insert_file(key, modified, size, extracted);
+            true
+        }
+        Some(_) => index.write().unwrap().remove_file(&key),
+        None if path.is_dir() => search_index::files_under(path)
+            .iter()
+            .fold(false, |changed, file| update_path(index, data_dir, file) | changed),
+        None => {
+            let prefix = format!("{}{}", key.trim_end_matches(std::path::MAIN_SEPARATOR), std::path::MAIN_SEPARATOR);
+            let mut index = index.write().unwrap();
+            let removed: Vec<String> = index
+                .files
+                .keys()
+                .filter(|p| **p == key || p.starts_with(&prefix))
+                .cloned()
+                .collect();
+            removed.iter().fold(false, |changed, p| index.remove_file(p) | changed)
+        }
+    }
+}
+
+/// 与磁盘上的文件对齐
+pub(crate) fn sync_with_disk(index: &RwLock<SemanticIndex>, data_dir: &Path) {
+    index.write().unwrap().remove_excluded(data_dir);
+
+    let files = search_index::files_under(data_dir);
+    for path in &files {
+        update_path(index, data_dir, path);
+    }
+
+    let existing: std::collections::HashSet<String> =
+        files.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    let mut index = index.write().unwrap();
+    let stale: Vec<String> = index
+        .files
+        .keys()
+        .filter(|p| !existing.contains(*p))
+        .cloned()
+        .collect();
+    for path in stale {
+        index.remove_file(&path);
+    }
+}
+
+fn get_semantic_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(search_index::get_index_dir(app)?.join(SEMANTIC_FILE))
+}
+
+pub(crate) fn load_index(app: &AppHandle) -> SemanticIndex {
+    let loaded = get_semantic_path(app)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|contents| serde_json::from_str::<SemanticIndex>(&contents).map_err(|e| e.to_string()));
+    match loaded {
+        Ok(index) if index.version == SEMANTIC_VERSION => index,
+        _ => SemanticIndex::empty(),
+    }
+}
+
+pub(crate) fn save_index(app: &AppHandle, index: &RwLock<SemanticIndex>) -> Result<(), String> {
+    let path = get_semantic_path(app)?;
+    let contents = serde_json::to_vec(&*index.read().unwrap()).map_err(|e| e.to_string())?;
+    atomic_file::write_atomic(&path, &contents).map_err(|e| e.to_string())
+}
+
+/// 丢弃现有数据并重新扫描
+pub(crate) fn rebuild(index: &RwLock<SemanticIndex>, data_dir: &Path) {
+    let rebuilt = RwLock::new(SemanticIndex::empty());
+    sync_with_disk(&rebuilt, data_dir);
+    *index.write().unwrap() = rebuilt.into_inner().unwrap();
+}
+
+// --- Tauri Commands ---
+
+/// 语义检索聊天消息和世界书条目，返回最相关的 top_k 个单元
+/// kinds 限定单元类型，paths 限定文件 (例如只在某个世界书中检索)
+#[tauri::command]
+pub async fn semantic_search(
+    state: State<'_, SemanticIndexState>,
+    query: String,
+    top_k: Option<usize>,
+    kinds: Option<Vec<UnitKind>>,
+    paths: Option<Vec<String>>,
+) -> Result<Vec<SemanticHit>, String> {
+    let index = state.index.clone();
+    let top_k = top_k.unwrap_or(DEFAULT_TOP_K);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let index = index.read().unwrap();
+        let filter = |unit: &Unit| {
+            kinds.as_ref().map(|k| k.contains(&unit.kind)).unwrap_or(true)
+                && paths.as_ref().map(|p| p.contains(&unit.path)).unwrap_or(true)
+        };
+        index
+            .query(&query, filter)
+            .into_iter()
+            .take(top_k)
+            .filter_map(|(id, score)| {
+                index.units.get(&id).map(|unit| SemanticHit {
+                    path: unit.path.clone(),
+                    pointer: unit.pointer.clone(),
+                    kind: unit.kind,
+                    title: unit.title.clone(),
+                    role: unit.role.clone(),
+                    preview: unit.preview.clone(),
+                    score,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(text: &str) -> ExtractedUnit {
+        ExtractedUnit {
+            pointer: "/entries/0".into(),
+            kind: UnitKind::LorebookEntry,
+            title: String::new(),
+            role: None,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn previously_indexed_secret_files_are_removed() {
+        let data_dir = Path::new("/data");
+        let path = |file: &str| data_dir.join(file).to_string_lossy().to_string();
+        let mut index = SemanticIndex::empty();
+        index.insert_file(
+            path("secret_profiles.json"),
+            0,
+            0,
+            vec![unit("default profile")],
+        );
+        index.insert_file(path("secrets.json"), 0, 0, vec![unit("openai key")]);
+        index.insert_file(path("lore.json"), 0, 0, vec![unit("the dragon sleeps")]);
+
+        assert!(index.remove_excluded(data_dir));
+        assert_eq!(
+            index.files.keys().collect::<Vec<_>>(),
+            vec![&path("lore.json")]
+        );
+        assert_eq!(index.units.len(), 1);
+        assert!(index.query("openai key", |_| true).is_empty());
+        assert_eq!(index.query("dragon", |_| true).len(), 1);
+    }
+
+    #[test]
+    fn extracts_chat_alternatives_and_lorebook_entries() {
+        let chat = serde_json::json!({
+            "name": "Tavern",
+            "messages": [
+                { "role": "user", "alternatives": [{ "content": "Hello there" }] },
+                {
+                    "role": "assistant",
+                    "alternatives": [
+                        { "content": "First reply" },
+                        { "content": "   " },
+                        {
+                            "type": "branch",
+                            "messages": [
+                                { "role": "user", "alternatives": [{ "content": "Branch message" }] }
+                            ]
+                        }
+                    ]
+                }
+            ]
+        });
+        let units = extract_units(&chat);
+        let summary: Vec<(&str, Option<&str>, &str)> = units
+            .iter()
+            .map(|u| (u.pointer.as_str(), u.role.as_deref(), u.text.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/messages/0/alternatives/0", Some("user"), "Hello there"),
+                (
+                    "/messages/1/alternatives/0",
+                    Some("assistant"),
+                    "First reply"
+                ),
+                (
+                    "/messages/1/alternatives/2/messages/0/alternatives/0",
+                    Some("user"),
+                    "Branch message"
+                ),
+            ]
+        );
+        assert!(units
+            .iter()
+            .all(|u| u.kind == UnitKind::ChatMessage && u.title == "Tavern"));
+
+        let lorebook = serde_json::json!({
+            "entries": [
+                { "name": "Excalibur", "description": "A sword", "activationEffect": { "content": "Drawn from the stone" } },
+                { "name": "No effect" },
+                { "name": "Dragon", "activationEffect": { "content": "Sleeps under the mountain" } }
+            ]
+        });
+        let units = extract_units(&lorebook);
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].pointer, "/entries/0");
+        assert_eq!(units[0].kind, UnitKind::LorebookEntry);
+        assert_eq!(units[0].title, "Excalibur");
+        assert_eq!(units[0].text, "Excalibur\nA sword\nDrawn from the stone");
+        assert_eq!(units[1].pointer, "/entries/2");
+
+        assert!(extract_units(&serde_json::json!({ "settings": { "theme": "dark" } })).is_empty());
+    }
+
+    #[test]
+    fn paraphrased_query_ranks_the_matching_message_first() {
+        let mut index = SemanticIndex::empty();
+        let texts = [
+            "The dragon sleeps under the mountain",
+            "Aria argued with the blacksmith about the price of her sword",
+            "They shared bread and stories by the fire",
+            "The blacksmith sold a new shield to the guard",
+        ];
+        for (i, text) in texts.iter().enumerate() {
+            index.insert_file(format!("/data/chat{}.json", i), 0, 0, vec![unit(text)]);
+        }
+
+        let ranked = index.query("arguing about a sword", |_| true);
+        let top = &index.units[&ranked[0].0];
+        assert_eq!(top.path, "/data/chat1.json");
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+        // 没有共同特征的单元不会出现在结果中
+        assert!(ranked
+            .iter()
+            .all(|(id, _)| index.units[id].path != "/data/chat2.json"));
+
+        let filtered = index.query("arguing about a sword", |u| u.path != "/data/chat1.json");
+        assert!(filtered
+            .iter()
+            .all(|(id, _)| index.units[id].path != "/data/chat1.json"));
+    }
+
+    #[test]
+    fn changed_files_are_reindexed_incrementally() {
+        let data_dir = std::env::temp_dir().join(format!("semantic-update-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&data_dir);
+        fs::create_dir_all(data_dir.join("chats")).unwrap();
+        let path = data_dir.join("chats/tavern.json");
+        let write = |content: &str| {
+            let chat = serde_json::json!({ "messages": [{ "role": "user", "alternatives": [{ "content": content }] }] });
+            fs::write(&path, chat.to_string()).unwrap();
+        };
+        let index = RwLock::new(SemanticIndex::empty());
+        let hits = |query: &str| index.read().unwrap().query(query, |_| true).len();
+
+        write("The dragon sleeps");
+        assert!(update_path(&index, &data_dir, &path));
+        assert!(!update_path(&index, &data_dir, &path));
+        assert_eq!(hits("dragon"), 1);
+
+        write("The knight polished his shield");
+        assert!(update_path(&index, &data_dir, &path));
+        assert_eq!(hits("dragon"), 0);
+        assert_eq!(hits("shield"), 1);
+        assert_eq!(index.read().unwrap().units.len(), 1);
+
+        fs::remove_dir_all(data_dir.join("chats")).unwrap();
+        assert!(update_path(&index, &data_dir, &data_dir.join("chats")));
+        assert_eq!(hits("shield"), 0);
+        assert!(index.read().unwrap().files.is_empty());
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+// src-tauri/src/secrets_audit.rs
+
+use crate::secrets_manager;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+pub(crate) const AUDIT_FILE: &str = "secrets_audit.jsonl";
+// 日志超过 MAX_AUDIT_SIZE 后轮转到这里，只保留一份，查询时一并读取
+pub(crate) const ROTATED_AUDIT_FILE: &str = "secrets_audit.old.jsonl";
+const MAX_AUDIT_SIZE: u64 = 4 * 1024 * 1024;
+const DEFAULT_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    // 代理把密钥替换进请求
+    Substitute,
+    // 代理因使用限制拒绝了请求，或用户拒绝了脚本的密钥请求
+    Denied,
+    // 用户同意后，密钥被交给了请求它的脚本
+    ScriptGrant,
+    // 用户明确允许后，设有使用限制的密钥被交给了脚本
+    ScriptGrantRestricted,
+    Write,
+    Delete,
+    Rollback,
+    Import,
+    // 通过 test_secret 验证密钥
+    Test,
+    // 日志被清空 (清空后写入的第一条)
+    Clear,
+}
+
+// 只记录密钥名称，绝不记录值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub key: Option<String>,
+    pub profile: Option<String>,
+    pub host: Option<String>,
+    pub method: Option<String>,
+    pub status: Option<u16>,
+    // 发起请求的来源 (Origin 或 Referer)
+    pub origin: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, key: Option<&str>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action,
+            key: key.map(str::to_string),
+            profile: None,
+            host: None,
+            method: None,
+            status: None,
+            origin: None,
+        }
+    }
+}
+
+// 串行化日志文件的追加、轮转和清空
+#[derive(Default)]
+pub struct SecretsAuditState {
+    lock: Mutex<()>,
+}
+
+fn get_audit_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+// 追加到 dir 下的日志；超过 max_size 时先把当前文件轮转为旧文件 (覆盖更早的旧文件)
+fn append_file(dir: &Path, entries: &[AuditEntry], max_size: u64) -> Result<(), String> {
+    let mut buf = String::new();
+    for entry in entries {
+        buf.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+        buf.push('\n');
+    }
+    let path = dir.join(AUDIT_FILE);
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= max_size) {
+        fs::rename(&path, dir.join(ROTATED_AUDIT_FILE)).map_err(|e| e.to_string())?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    file.write_all(buf.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn read_file(path: &Path) -> Vec<AuditEntry> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        // 跳过损坏的行 (例如写入时崩溃留下的半行)
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+        .collect()
+}
+
+// 读取 dir 下的旧文件和当前文件，按条件过滤后按时间倒序返回
+fn query_dir(
+    dir: &Path,
+    key: Option<&str>,
+    action: Option<AuditAction>,
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: usize,
+) -> Vec<AuditEntry> {
+    let mut entries: Vec<AuditEntry> = read_file(&dir.join(ROTATED_AUDIT_FILE))
+        .into_iter()
+        .chain(read_file(&dir.join(AUDIT_FILE)))
+        .filter(|e| key.is_none_or(|k| e.key.as_deref() == Some(k)))
+        .filter(|e| action.is_none_or(|a| e.action == a))
+        .filter(|e| since.is_none_or(|t| e.timestamp.timestamp_millis() >= t))
+        .filter(|e| until.is_none_or(|t| e.timestamp.timestamp_millis() <= t))
+        .collect();
+    // 后台写入可能让相邻的记录乱序，按时间排序 (稳定排序，同一时间保持写入顺序)
+    entries.reverse();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+    entries.truncate(limit);
+    entries
+}
+
+/// 追加日志；在后台线程写入，不阻塞调用方 (例如代理的请求处理)。
+/// 写日志失败不影响正常功能，只打印错误
+pub fn record(app: &AppHandle, entries: Vec<AuditEntry>) {
+    if entries.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let profile = secrets_manager::active_profile_name(&app);
+        let entries: Vec<AuditEntry> = entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.profile = entry.profile.or_else(|| profile.clone());
+                entry
+            })
+            .collect();
+        let result = get_audit_dir(&app).and_then(|dir| {
+            let _guard = app.state::<SecretsAuditState>().lock.lock().unwrap();
+            append_file(&dir, &entries, MAX_AUDIT_SIZE)
+        });
+        if let Err(e) = result {
+            eprintln!("[SecretsAudit] Failed to write audit log: {}", e);
+        }
+    });
+}
+
+/// 记录密钥的写入、删除等修改
+pub fn record_change(app: &AppHandle, action: AuditAction, key: &str) {
+    record(app, vec![AuditEntry::new(action, Some(key))]);
+}
+
+// --- Tauri Commands ---
+
+/// 查询审计日志，按时间倒序；since/until 为毫秒时间戳
+#[tauri::command]
+pub fn query_secret_audit(
+    app: AppHandle,
+    key: Option<String>,
+    action: Option<AuditAction>,
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEntry>, String> {
+    let dir = get_audit_dir(&app)?;
+    Ok(query_dir(
+        &dir,
+        key.as_deref(),
+        action,
+        since,
+        until,
+        limit.unwrap_or(DEFAULT_LIMIT),
+    ))
+}
+
+/// 清空审计日志 (包括轮转出的旧文件)，并记录一条清空操作
+#[tauri::command]
+pub fn clear_secret_audit(app: AppHandle, state: State<'_, SecretsAuditState>) -> Result<(), String> {
+    let dir = get_audit_dir(&app)?;
+    {
+        let _guard = state.lock.lock().unwrap();
+        for name in [AUDIT_FILE, ROTATED_AUDIT_FILE] {
+            let path = dir.join(name);
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    record(&app, vec![AuditEntry::new(AuditAction::Clear, None)]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(action: AuditAction, key: &str, millis: i64) -> AuditEntry {
+        AuditEntry {
+            timestamp: Utc.timestamp_millis_opt(millis).unwrap(),
+            ..AuditEntry::new(action, Some(key))
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("secrets-audit-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn keys(entries: &[AuditEntry]) -> Vec<(&str, i64)> {
+        entries
+            .iter()
+            .map(|e| (e.key.as_deref().unwrap(), e.timestamp.timestamp_millis()))
+            .collect()
+    }
+
+    #[test]
+    fn query_filters_by_key_action_and_time() {
+        let dir = temp_dir("filter");
+        let entries = vec![
+            entry(AuditAction::Write, "a", 1000),
+            entry(AuditAction::Substitute, "a", 2000),
+            entry(AuditAction::Substitute, "b", 3000),
+            entry(AuditAction::Substitute, "a", 4000),
+        ];
+        append_file(&dir, &entries, MAX_AUDIT_SIZE).unwrap();
+
+        let all = query_dir(&dir, None, None, None, None, DEFAULT_LIMIT);
+        assert_eq!(
+            keys(&all),
+            vec![("a", 4000), ("b", 3000), ("a", 2000), ("a", 1000)]
+        );
+
+        let by_key = query_dir(&dir, Some("a"), None, None, None, DEFAULT_LIMIT);
+        assert_eq!(keys(&by_key), vec![("a", 4000), ("a", 2000), ("a", 1000)]);
+
+        let by_action = query_dir(
+            &dir,
+            Some("a"),
+            Some(AuditAction::Write),
+            None,
+            None,
+            DEFAULT_LIMIT,
+        );
+        assert_eq!(keys(&by_action), vec![("a", 1000)]);
+
+        // since/until 均包含边界
+        let by_time = query_dir(&dir, None, None, Some(2000), Some(3000), DEFAULT_LIMIT);
+        assert_eq!(keys(&by_time), vec![("b", 3000), ("a", 2000)]);
+
+        let limited = query_dir(&dir, None, None, None, None, 2);
+        assert_eq!(keys(&limited), vec![("a", 4000), ("b", 3000)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn query_skips_corrupt_lines() {
+        let dir = temp_dir("corrupt");
+        append_file(
+            &dir,
+            &[entry(AuditAction::Write, "a", 1000)],
+            MAX_AUDIT_SIZE,
+        )
+        .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(AUDIT_FILE))
+            .unwrap();
+        file.write_all(b"not json\n{\"timestamp\":12").unwrap();
+        file.write_all(b"\n").unwrap();
+        append_file(
+            &dir,
+            &[entry(AuditAction::Delete, "b", 2000)],
+            MAX_AUDIT_SIZE,
+        )
+        .unwrap();
+
+        let entries = query_dir(&dir, None, None, None, None, DEFAULT_LIMIT);
+        assert_eq!(keys(&entries), vec![("b", 2000), ("a", 1000)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn large_log_is_rotated_and_still_queried() {
+        let dir = temp_dir("rotate");
+        append_file(&dir, &[entry(AuditAction::Write, "a", 1000)], 1).unwrap();
+        // 当前文件已超过上限：轮转后写入新文件
+        append_file(&dir, &[entry(AuditAction::Write, "b", 2000)], 1).unwrap();
+        assert!(dir.join(ROTATED_AUDIT_FILE).exists());
+        assert_eq!(
+            keys(&query_dir(&dir, None, None, None, None, DEFAULT_LIMIT)),
+            vec![("b", 2000), ("a", 1000)]
+        );
+
+        // 再次轮转会覆盖更早的旧文件，只保留两份
+        append_file(&dir, &[entry(AuditAction::Write, "c", 3000)], 1).unwrap();
+        assert_eq!(
+            keys(&query_dir(&dir, None, None, None, None, DEFAULT_LIMIT)),
+            vec![("c", 3000), ("b", 2000)]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
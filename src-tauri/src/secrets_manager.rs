@@ -1,12 +1,19 @@
 // src-tauri/src/secrets_manager.rs
 
-use serde::{Serialize};
-use serde_json;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use crate::atomic_file;
+use crate::secrets_audit::{self, AuditAction};
+use crate::secrets_vault::{self, KdfParams, VaultEnvelope, VaultKey};
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use zeroize::Zeroize;
 
 // 定义错误类型，方便在命令中返回
 #[derive(Debug, thiserror::Error)]
@@ -17,6 +24,18 @@ pub enum Error {
     Json(#[from] serde_json::Error),
     #[error("Failed to get app data directory")]
     AppDataDir,
+    #[error("Secrets are locked")]
+    Locked,
+    #[error("Wrong master password")]
+    WrongPassword,
+    #[error("Invalid vault file: {0}")]
+    InvalidVault(String),
+    #[error("Encryption error: {0}")]
+    Crypto(String),
+    #[error("Secret not found: {0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidInput(String),
 }
 
 // 为了让错误能被 Tauri 正确序列化并发送到前端
@@ -29,43 +48,513 @@ impl Serialize for Error {
     }
 }
 
-// 辅助函数：获取 secrets.json 的完整路径
-// 如果文件或目录不存在，则创建它们
-fn get_secrets_path(app: &AppHandle) -> Result<PathBuf, Error> {
+// plaintext: 旧版未加密的 secrets.json，首次解锁时会被加密
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultStatus {
+    Plaintext,
+    Locked,
+    Unlocked,
+}
+
+// 每个密钥保留的历史值数量，用于轮换出错后回滚
+const MAX_HISTORY: usize = 5;
+// 档案列表及当前档案
+const PROFILES_FILE: &str = "secret_profiles.json";
+// 新建档案的密钥文件所在目录
+const PROFILES_DIR: &str = "secret_profiles";
+const DEFAULT_PROFILE: &str = "default";
+const DEFAULT_SECRETS_FILE: &str = "secrets.json";
+const MAX_PROFILE_NAME_LEN: usize = 64;
+// 数据目录中保存密钥、档案和审计日志的文件或目录，全文搜索和索引都必须跳过
+pub(crate) const SECRET_FILES: &[&str] = &[
+    DEFAULT_SECRETS_FILE,
+    PROFILES_DIR,
+    PROFILES_FILE,
+    secrets_audit::AUDIT_FILE,
+    secrets_audit::ROTATED_AUDIT_FILE,
+];
+
+// 密钥的元数据 (不包含值)
+// 使用限制均为空时不做限制 (兼容旧数据)；旧数据没有时间戳
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SecretMeta {
+    // 允许发送到的主机，例如 "api.openai.com"、"*.openai.azure.com"、"localhost:11434"
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    // 允许出现在哪些请求头中 (不区分大小写)，为空时不限制
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    // 服务商标记，例如 "openai"、"anthropic"
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// 被替换掉的旧值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretRevision {
+    pub value: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub replaced_at: DateTime<Utc>,
+}
+
+// 文件 (或加密内容) 中保存的数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecretStore {
+    pub secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub meta: HashMap<String, SecretMeta>,
+    // 每个密钥的历史值，最新的在前
+    #[serde(default)]
+    pub history: HashMap<String, Vec<SecretRevision>>,
+}
+
+// 兼容旧版只有键值对的格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSecrets {
+    Store(SecretStore),
+    Legacy(HashMap<String, String>),
+}
+
+impl SecretStore {
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(match serde_json::from_slice::<StoredSecrets>(bytes)? {
+            StoredSecrets::Store(store) => store,
+            StoredSecrets::Legacy(secrets) => {
+                let mut store = SecretStore::default();
+                store.secrets = secrets;
+                store
+            }
+        })
+    }
+
+    // 清零所有密钥的值 (包括历史值)
+    fn zeroize_values(&mut self) {
+        self.secrets.values_mut().for_each(|value| value.zeroize());
+        self.history
+            .values_mut()
+            .flatten()
+            .for_each(|revision| revision.value.zeroize());
+    }
+
+    // 设置新值，旧值进入历史记录
+    pub(crate) fn set_value(&mut self, key: &str, value: String, now: DateTime<Utc>) {
+        let meta = self.meta.entry(key.to_string()).or_default();
+        match self.secrets.insert(key.to_string(), value.clone()) {
+            Some(old) if old != value => {
+                let history = self.history.entry(key.to_string()).or_default();
+                history.insert(0, SecretRevision { value: old, replaced_at: now });
+                history.truncate(MAX_HISTORY);
+            }
+            Some(_) => {}
+            None => meta.created_at = Some(now),
+        }
+        meta.updated_at = Some(now);
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        self.meta.remove(key);
+        self.history.remove(key);
+        self.secrets.remove(key).is_some()
+    }
+}
+
+// 解密后的密钥只在内存中保留到最后一个引用被释放 (锁定、切换档案或被新版本替换)，释放时清零
+impl Drop for SecretStore {
+    fn drop(&mut self) {
+        self.zeroize_values();
+    }
+}
+
+// 列表中展示的密钥信息，不包含任何值
+#[derive(Debug, Serialize)]
+pub struct SecretInfo {
+    pub key: String,
+    #[serde(flatten)]
+    pub meta: SecretMeta,
+    pub expired: bool,
+    // 可回滚的历史版本的替换时间 (毫秒)，最新的在前
+    pub history: Vec<i64>,
+}
+
+// 解锁后的密钥只保存在内存中
+struct UnlockedVault {
+    store: Arc<SecretStore>,
+    key: VaultKey,
+    kdf: KdfParams,
+}
+
+// 内存中的缓存，代理每次请求只读取这里，不再访问磁盘
+#[derive(Default)]
+struct SecretsCache {
+    // 最近一次读取或写入的文件内容，用于识别文件监听收到的是否是自己的写入；
+    // None 表示需要重新读取磁盘
+    contents: Option<String>,
+    // 明文文件的解析结果 (加密文件为 None)
+    plaintext: Option<Arc<SecretStore>>,
+    unlocked: Option<UnlockedVault>,
+}
+
+// 密钥档案：每个档案是一个独立的密钥文件 (可各自设置主密码)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileSettings {
+    active: String,
+    // 档案名 -> 相对于 app_data_dir 的文件路径；重命名只改名称，不移动文件
+    profiles: BTreeMap<String, String>,
+}
+
+impl Default for ProfileSettings {
+    // 旧版只有一个 secrets.json，作为默认档案
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.to_string(), DEFAULT_SECRETS_FILE.to_string())]),
+        }
+    }
+}
+
+impl ProfileSettings {
+    fn active_file(&self) -> &str {
+        self.profiles
+            .get(&self.active)
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_SECRETS_FILE)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct SecretsState {
+    // 当前档案的缓存
+    cache: Mutex<SecretsCache>,
+    // 切换档案时，已解锁的非当前档案暂存在这里 (按文件名)，切回时无需再次输入密码
+    parked: Mutex<HashMap<String, UnlockedVault>>,
+    profiles: Mutex<Option<ProfileSettings>>,
+    // 串行化“读取-修改-写入”，防止并发的写入命令互相覆盖
+    write_lock: Mutex<()>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+fn get_app_data_dir(app: &AppHandle) -> Result<PathBuf, Error> {
     let app_data_dir = app.path()
         .app_data_dir()
         .map_err(|_| Error::AppDataDir)?;
 
     // 确保 app_data_dir 存在
     fs::create_dir_all(&app_data_dir)?;
+    Ok(app_data_dir)
+}
+
+// 读取档案设置 (首次读取后保存在内存中)
+fn with_profiles<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut ProfileSettings) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let state = app.state::<SecretsState>();
+    let mut profiles = state.profiles.lock().unwrap();
+    if profiles.is_none() {
+        let path = get_app_data_dir(app)?.join(PROFILES_FILE);
+        *profiles = Some(if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            ProfileSettings::default()
+        });
+    }
+    f(profiles.as_mut().unwrap())
+}
 
-    let secrets_file_path = app_data_dir.join("secrets.json");
+// 修改档案设置并写回磁盘；失败时内存中的设置保持不变
+fn update_profiles<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut ProfileSettings) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let path = get_app_data_dir(app)?.join(PROFILES_FILE);
+    with_profiles(app, |profiles| {
+        let mut updated = profiles.clone();
+        let result = f(&mut updated)?;
+        atomic_file::write_atomic(&path, serde_json::to_string_pretty(&updated)?.as_bytes())?;
+        *profiles = updated;
+        Ok(result)
+    })
+}
 
-    // 如果 secrets.json 文件不存在，则创建一个空的 JSON 对象文件
+/// 当前档案名，供审计日志使用
+pub fn active_profile_name(app: &AppHandle) -> Option<String> {
+    with_profiles(app, |p| Ok(p.active.clone())).ok()
+}
+
+// 辅助函数：获取当前档案密钥文件的完整路径
+// 如果文件或目录不存在，则创建它们
+fn get_secrets_path(app: &AppHandle) -> Result<PathBuf, Error> {
+    let app_data_dir = get_app_data_dir(app)?;
+    let secrets_file_path = app_data_dir.join(with_profiles(app, |p| Ok(p.active_file().to_string()))?);
+
+    // 如果文件不存在，则创建一个空的 JSON 对象文件
     if !secrets_file_path.exists() {
-        let mut file = File::create(&secrets_file_path)?;
-        file.write_all(b"{}")?; // 写入一个空的 JSON 对象
+        if let Some(parent) = secrets_file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic_file::write_atomic(&secrets_file_path, b"{}")?;
     }
 
     Ok(secrets_file_path)
 }
 
-// 辅助函数：从文件读取密钥
-pub fn read_secrets(app: &AppHandle) -> Result<HashMap<String, String>, Error> {
-    let path = get_secrets_path(app)?;
-    let mut file = File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let secrets: HashMap<String, String> = serde_json::from_str(&contents)?;
-    Ok(secrets)
+fn file_name_of(relative: &str) -> String {
+    Path::new(relative)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn validate_profile_name(name: &str) -> Result<String, Error> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_PROFILE_NAME_LEN {
+        return Err(Error::InvalidInput(format!(
+            "Profile name must be 1-{} characters",
+            MAX_PROFILE_NAME_LEN
+        )));
+    }
+    Ok(name.to_string())
+}
+
+fn read_file(app: &AppHandle) -> Result<String, Error> {
+    Ok(fs::read_to_string(get_secrets_path(app)?)?)
 }
 
-// 辅助函数：将密钥写入文件
-fn write_secrets(app: &AppHandle, secrets: &HashMap<String, String>) -> Result<(), Error> {
+// 先写临时文件再 rename，写入过程中崩溃不会丢失已有的密钥
+fn write_file(app: &AppHandle, contents: &[u8]) -> Result<(), Error> {
     let path = get_secrets_path(app)?;
-    let contents = serde_json::to_string_pretty(secrets)?;
-    let mut file = File::create(path)?;
-    file.write_all(contents.as_bytes())?;
+    atomic_file::write_atomic(&path, contents)?;
+    Ok(())
+}
+
+// 加密为文件内容
+fn seal_store(key: &VaultKey, kdf: &KdfParams, store: &SecretStore) -> Result<String, Error> {
+    let plaintext = zeroize::Zeroizing::new(serde_json::to_vec(store)?);
+    let envelope = secrets_vault::seal(key, kdf, &plaintext)?;
+    Ok(serde_json::to_string_pretty(&envelope)?)
+}
+
+fn open_store(key: &VaultKey, contents: &str) -> Result<SecretStore, Error> {
+    let envelope = VaultEnvelope::parse(contents)?;
+    let plaintext = secrets_vault::open(key, &envelope)?;
+    SecretStore::parse(&plaintext)
+}
+
+impl SecretsCache {
+    // 缓存失效时从磁盘重新读取
+    fn ensure_loaded(&mut self, app: &AppHandle) -> Result<(), Error> {
+        if self.contents.is_some() {
+            return Ok(());
+        }
+        let contents = read_file(app)?;
+        self.plaintext = if secrets_vault::is_vault(&contents) {
+            None
+        } else {
+            Some(Arc::new(SecretStore::parse(contents.as_bytes())?))
+        };
+        self.contents = Some(contents);
+        Ok(())
+    }
+
+    fn invalidate(&mut self) {
+        self.contents = None;
+        self.plaintext = None;
+    }
+}
+
+fn vault_status(app: &AppHandle) -> Result<VaultStatus, Error> {
+    let state = app.state::<SecretsState>();
+    let mut cache = state.cache.lock().unwrap();
+    if cache.unlocked.is_some() {
+        return Ok(VaultStatus::Unlocked);
+    }
+    cache.ensure_loaded(app)?;
+    if cache.plaintext.is_some() {
+        Ok(VaultStatus::Plaintext)
+    } else {
+        Ok(VaultStatus::Locked)
+    }
+}
+
+// 辅助函数：读取密钥及其元数据
+// 已解锁时直接返回内存中的数据；加密文件未解锁时返回 Locked
+pub fn read_store(app: &AppHandle) -> Result<Arc<SecretStore>, Error> {
+    let state = app.state::<SecretsState>();
+    let mut cache = state.cache.lock().unwrap();
+    if let Some(vault) = cache.unlocked.as_ref() {
+        return Ok(vault.store.clone());
+    }
+    cache.ensure_loaded(app)?;
+    cache.plaintext.clone().ok_or(Error::Locked)
+}
+
+// 辅助函数：只读取密钥的值
+pub fn read_secrets(app: &AppHandle) -> Result<HashMap<String, String>, Error> {
+    Ok(read_store(app)?.secrets.clone())
+}
+
+// 辅助函数：将密钥写入文件并更新缓存
+// 已解锁时重新加密写入；未加密的文件仍按明文写入
+fn write_store(app: &AppHandle, store: SecretStore) -> Result<(), Error> {
+    let state = app.state::<SecretsState>();
+    let mut cache = state.cache.lock().unwrap();
+    let store = Arc::new(store);
+    let contents = match cache.unlocked.as_ref() {
+        Some(vault) => seal_store(&vault.key, &vault.kdf, &store)?,
+        None => {
+            cache.ensure_loaded(app)?;
+            if cache.plaintext.is_none() {
+                return Err(Error::Locked);
+            }
+            serde_json::to_string_pretty(&*store)?
+        }
+    };
+    write_file(app, contents.as_bytes())?;
+    match cache.unlocked.as_mut() {
+        Some(vault) => vault.store = store,
+        None => cache.plaintext = Some(store),
+    }
+    cache.contents = Some(contents);
+    Ok(())
+}
+
+// 在写锁内读取、修改并写回
+pub(crate) fn update_store<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut SecretStore) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let state = app.state::<SecretsState>();
+    let _guard = state.write_lock.lock().unwrap();
+    let mut store = SecretStore::clone(&read_store(app)?);
+    let result = f(&mut store)?;
+    write_store(app, store)?;
+    Ok(result)
+}
+
+// 用密码解锁；明文文件会以该密码加密 (迁移)
+fn unlock(app: &AppHandle, password: &str) -> Result<(), Error> {
+    let state = app.state::<SecretsState>();
+    let _guard = state.write_lock.lock().unwrap();
+    let mut contents = read_file(app)?;
+    let vault = if secrets_vault::is_vault(&contents) {
+        let kdf = VaultEnvelope::parse(&contents)?.kdf().clone();
+        let key = secrets_vault::derive_key(password, &kdf)?;
+        let store = Arc::new(open_store(&key, &contents)?);
+        UnlockedVault { store, key, kdf }
+    } else {
+        let store = Arc::new(SecretStore::parse(contents.as_bytes())?);
+        let kdf = KdfParams::generate();
+        let key = secrets_vault::derive_key(password, &kdf)?;
+        contents = seal_store(&key, &kdf, &store)?;
+        write_file(app, contents.as_bytes())?;
+        UnlockedVault { store, key, kdf }
+    };
+    let mut cache = state.cache.lock().unwrap();
+    cache.plaintext = None;
+    cache.contents = Some(contents);
+    cache.unlocked = Some(vault);
+    Ok(())
+}
+
+// 验证旧密码后，用新密码 (新的盐) 重新加密
+fn change_password(app: &AppHandle, old_password: &str, new_password: &str) -> Result<(), Error> {
+    let state = app.state::<SecretsState>();
+    let _guard = state.write_lock.lock().unwrap();
+    let contents = read_file(app)?;
+    if !secrets_vault::is_vault(&contents) {
+        return Err(Error::InvalidVault("Secrets are not encrypted yet".to_string()));
+    }
+    let old_kdf = VaultEnvelope::parse(&contents)?.kdf().clone();
+    let old_key = secrets_vault::derive_key(old_password, &old_kdf)?;
+    let store = Arc::new(open_store(&old_key, &contents)?);
+
+    let kdf = KdfParams::generate();
+    let key = secrets_vault::derive_key(new_password, &kdf)?;
+    let contents = seal_store(&key, &kdf, &store)?;
+    write_file(app, contents.as_bytes())?;
+
+    let mut cache = state.cache.lock().unwrap();
+    cache.plaintext = None;
+    cache.contents = Some(contents);
+    cache.unlocked = Some(UnlockedVault { store, key, kdf });
+    Ok(())
+}
+
+// 文件被外部修改 (同步工具、手动编辑等) 时刷新缓存
+fn reload_from_disk(app: &AppHandle) {
+    let state = app.state::<SecretsState>();
+    // 等待进行中的写入完成，这样自己的写入可以通过内容比较识别出来
+    let _guard = state.write_lock.lock().unwrap();
+    let contents = read_file(app).ok();
+    let mut cache = state.cache.lock().unwrap();
+    if contents.is_some() && cache.contents == contents {
+        return;
+    }
+    cache.invalidate();
+    // 已解锁时用现有密钥重新解密；失败 (例如密码在别处被修改) 则重新锁定
+    if let (Some(vault), Some(contents)) = (cache.unlocked.take(), contents) {
+        if secrets_vault::is_vault(&contents) {
+            if let Ok(store) = open_store(&vault.key, &contents) {
+                cache.unlocked = Some(UnlockedVault { store: Arc::new(store), ..vault });
+                cache.contents = Some(contents);
+            }
+        }
+    }
+}
+
+// 文件监听回调：当前档案刷新缓存，暂存的档案直接丢弃 (需要重新解锁)
+fn on_file_changed(app: &AppHandle, file_name: &str) {
+    let active = with_profiles(app, |p| Ok(file_name_of(p.active_file())));
+    if active.is_ok_and(|active| active == file_name) {
+        reload_from_disk(app);
+    } else {
+        let state = app.state::<SecretsState>();
+        state.parked.lock().unwrap().retain(|file, _| file_name_of(file) != file_name);
+    }
+}
+
+/// 监听各档案密钥文件的变化，在 setup 中调用
+pub fn init_secrets_watcher(app: &AppHandle) -> Result<(), String> {
+    let app_data_dir = get_app_data_dir(app).map_err(|e| e.to_string())?;
+    let profiles_dir = app_data_dir.join(PROFILES_DIR);
+    fs::create_dir_all(&profiles_dir).map_err(|e| e.to_string())?;
+    let handle = app.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            for path in &event.paths {
+                let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                // 忽略临时文件和档案设置文件
+                if name.ends_with(".json") && name != PROFILES_FILE && !name.starts_with('.') {
+                    on_file_changed(&handle, &name);
+                }
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    for dir in [&app_data_dir, &profiles_dir] {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| e.to_string())?;
+    }
+    *app.state::<SecretsState>().watcher.lock().unwrap() = Some(watcher);
     Ok(())
 }
 
@@ -74,24 +563,134 @@ fn write_secrets(app: &AppHandle, secrets: &HashMap<String, String>) -> Result<(
 
 #[tauri::command]
 pub fn write_secret_key(app: AppHandle, key: String, value: String) -> Result<(), String> {
-    let mut secrets = read_secrets(&app).map_err(|e| e.to_string())?;
-    secrets.insert(key, value);
-    write_secrets(&app, &secrets).map_err(|e| e.to_string())
+    update_store(&app, |store| {
+        store.set_value(&key, value, Utc::now());
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    secrets_audit::record_change(&app, AuditAction::Write, &key);
+    Ok(())
 }
 
 // 删除密钥命令
 #[tauri::command]
 pub fn delete_secret_key(app: AppHandle, key: String) -> Result<(), String> {
-    let mut secrets = read_secrets(&app).map_err(|e| e.to_string())?;
-
     // 如果键存在，则移除并保存文件
-    if secrets.remove(&key).is_some() {
-        write_secrets(&app, &secrets).map_err(|e| e.to_string())?;
+    let exists = read_store(&app).map_err(|e| e.to_string())?.secrets.contains_key(&key);
+    if exists {
+        update_store(&app, |store| Ok(store.remove(&key))).map_err(|e| e.to_string())?;
+        secrets_audit::record_change(&app, AuditAction::Delete, &key);
     }
 
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_secret_restrictions(app: AppHandle, key: String) -> Result<SecretMeta, String> {
+    let store = read_store(&app).map_err(|e| e.to_string())?;
+    if !store.secrets.contains_key(&key) {
+        return Err(Error::NotFound(key).to_string());
+    }
+    Ok(store.meta.get(&key).cloned().unwrap_or_default())
+}
+
+// 设置密钥允许发送到的主机和请求头，传入空列表表示不限制
+#[tauri::command]
+pub fn set_secret_restrictions(
+    app: AppHandle,
+    key: String,
+    allowed_hosts: Vec<String>,
+    allowed_headers: Vec<String>,
+) -> Result<(), String> {
+    let normalize = |items: Vec<String>| -> Vec<String> {
+        items
+            .into_iter()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    };
+    update_store(&app, |store| {
+        if !store.secrets.contains_key(&key) {
+            return Err(Error::NotFound(key.clone()));
+        }
+        let meta = store.meta.entry(key.clone()).or_default();
+        meta.allowed_hosts = normalize(allowed_hosts);
+        meta.allowed_headers = normalize(allowed_headers);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+// 列出所有密钥的元数据 (不返回值)
+#[tauri::command]
+pub fn list_secret_metadata(app: AppHandle) -> Result<Vec<SecretInfo>, String> {
+    let store = read_store(&app).map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    let mut list: Vec<SecretInfo> = store
+        .secrets
+        .keys()
+        .map(|key| {
+            let meta = store.meta.get(key).cloned().unwrap_or_default();
+            SecretInfo {
+                key: key.clone(),
+                expired: meta.expires_at.is_some_and(|t| t <= now),
+                history: store
+                    .history
+                    .get(key)
+                    .map(|h| h.iter().map(|r| r.replaced_at.timestamp_millis()).collect())
+                    .unwrap_or_default(),
+                meta,
+            }
+        })
+        .collect();
+    list.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(list)
+}
+
+// 更新描述、服务商和过期时间 (毫秒时间戳，None 表示不过期)
+#[tauri::command]
+pub fn update_secret_metadata(
+    app: AppHandle,
+    key: String,
+    description: String,
+    provider: Option<String>,
+    expires_at: Option<i64>,
+) -> Result<(), String> {
+    let expires_at = match expires_at {
+        Some(ms) => Some(
+            DateTime::<Utc>::from_timestamp_millis(ms).ok_or_else(|| "Invalid expiry time".to_string())?,
+        ),
+        None => None,
+    };
+    update_store(&app, |store| {
+        if !store.secrets.contains_key(&key) {
+            return Err(Error::NotFound(key.clone()));
+        }
+        let meta = store.meta.entry(key.clone()).or_default();
+        meta.description = description.trim().to_string();
+        meta.provider = provider.map(|p| p.trim().to_lowercase()).filter(|p| !p.is_empty());
+        meta.expires_at = expires_at;
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+// 回滚到第 index 个历史值 (0 为上一次的值)，当前值会进入历史记录
+#[tauri::command]
+pub fn rollback_secret_key(app: AppHandle, key: String, index: usize) -> Result<(), String> {
+    update_store(&app, |store| {
+        let revision = match store.history.get_mut(&key) {
+            Some(history) if index < history.len() => history.remove(index),
+            _ => return Err(Error::InvalidInput(format!("No such revision for secret: {}", key))),
+        };
+        store.set_value(&key, revision.value, Utc::now());
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    secrets_audit::record_change(&app, AuditAction::Rollback, &key);
+    Ok(())
+}
+
 #[tauri::command]
 pub fn is_key_available(app: AppHandle, key: String) -> Result<bool, String> {
     let secrets = read_secrets(&app).map_err(|e| e.to_string())?;
@@ -103,3 +702,206 @@ pub fn get_all_available_keys(app: AppHandle) -> Result<Vec<String>, String> {
     let secrets = read_secrets(&app).map_err(|e| e.to_string())?;
     Ok(secrets.keys().cloned().collect())
 }
+
+#[tauri::command]
+pub fn get_secrets_status(app: AppHandle) -> Result<VaultStatus, String> {
+    vault_status(&app).map_err(|e| e.to_string())
+}
+
+// 解锁 (密钥派生较慢，在后台线程执行)
+#[tauri::command]
+pub async fn unlock_secrets(app: AppHandle, password: String) -> Result<(), String> {
+    if password.is_empty() {
+        return Err("Master password must not be empty".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || unlock(&app, &password))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// 清除内存中 (所有档案) 的密钥和派生出的加密密钥
+#[tauri::command]
+pub fn lock_secrets(state: State<'_, SecretsState>) {
+    state.cache.lock().unwrap().unlocked.take();
+    state.parked.lock().unwrap().clear();
+}
+
+#[tauri::command]
+pub async fn change_master_password(
+    app: AppHandle,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    if new_password.is_empty() {
+        return Err("Master password must not be empty".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || change_password(&app, &old_password, &new_password))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_secret_profiles(app: AppHandle) -> Result<ProfileList, String> {
+    with_profiles(&app, |p| {
+        Ok(ProfileList {
+            active: p.active.clone(),
+            profiles: p.profiles.keys().cloned().collect(),
+        })
+    })
+    .map_err(|e| e.to_string())
+}
+
+// 新建一个空的档案 (不会自动切换)
+#[tauri::command]
+pub fn create_secret_profile(app: AppHandle, name: String) -> Result<(), String> {
+    let name = validate_profile_name(&name).map_err(|e| e.to_string())?;
+    let app_data_dir = get_app_data_dir(&app).map_err(|e| e.to_string())?;
+    update_profiles(&app, |p| {
+        if p.profiles.contains_key(&name) {
+            return Err(Error::InvalidInput(format!("Profile already exists: {}", name)));
+        }
+        let mut id = [0u8; 8];
+        OsRng.fill_bytes(&mut id);
+        let file = format!("{}/{}.json", PROFILES_DIR, hex::encode(id));
+        let path = app_data_dir.join(&file);
+        fs::create_dir_all(app_data_dir.join(PROFILES_DIR))?;
+        atomic_file::write_atomic(&path, b"{}")?;
+        p.profiles.insert(name.clone(), file);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn rename_secret_profile(app: AppHandle, old_name: String, new_name: String) -> Result<(), String> {
+    let new_name = validate_profile_name(&new_name).map_err(|e| e.to_string())?;
+    update_profiles(&app, |p| {
+        if p.profiles.contains_key(&new_name) {
+            return Err(Error::InvalidInput(format!("Profile already exists: {}", new_name)));
+        }
+        let file = p
+            .profiles
+            .remove(&old_name)
+            .ok_or_else(|| Error::InvalidInput(format!("Profile not found: {}", old_name)))?;
+        p.profiles.insert(new_name.clone(), file);
+        if p.active == old_name {
+            p.active = new_name.clone();
+        }
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+// 删除档案及其密钥文件；当前档案不能删除
+#[tauri::command]
+pub fn delete_secret_profile(app: AppHandle, name: String) -> Result<(), String> {
+    let state = app.state::<SecretsState>();
+    let _guard = state.write_lock.lock().unwrap();
+    let file = update_profiles(&app, |p| {
+        if p.active == name {
+            return Err(Error::InvalidInput("Cannot delete the active profile".to_string()));
+        }
+        p.profiles
+            .remove(&name)
+            .ok_or_else(|| Error::InvalidInput(format!("Profile not found: {}", name)))
+    })
+    .map_err(|e| e.to_string())?;
+
+    state.parked.lock().unwrap().remove(&file);
+    let path = get_app_data_dir(&app).map_err(|e| e.to_string())?.join(&file);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// 切换当前档案，代理随即使用新档案中的密钥
+#[tauri::command]
+pub fn switch_secret_profile(app: AppHandle, name: String) -> Result<(), String> {
+    let state = app.state::<SecretsState>();
+    let _guard = state.write_lock.lock().unwrap();
+    let mut cache = state.cache.lock().unwrap();
+    let (old_file, new_file) = update_profiles(&app, |p| {
+        let new_file = p
+            .profiles
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| Error::InvalidInput(format!("Profile not found: {}", name)))?;
+        let old_file = p.active_file().to_string();
+        p.active = name.clone();
+        Ok((old_file, new_file))
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut parked = state.parked.lock().unwrap();
+    if let Some(vault) = cache.unlocked.take() {
+        parked.insert(old_file, vault);
+    }
+    *cache = SecretsCache {
+        unlocked: parked.remove(&new_file),
+        ..Default::default()
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> (VaultKey, KdfParams) {
+        let kdf = KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            ..KdfParams::generate()
+        };
+        (secrets_vault::derive_key("pw", &kdf).unwrap(), kdf)
+    }
+
+    #[test]
+    fn plaintext_file_migrates_to_vault() {
+        // 旧版只有键值对的明文文件
+        let legacy = r#"{"OPENAI_API_KEY":"sk-test","OTHER":"value"}"#;
+        assert!(!secrets_vault::is_vault(legacy));
+        let store = SecretStore::parse(legacy.as_bytes()).unwrap();
+        assert_eq!(store.secrets["OPENAI_API_KEY"], "sk-test");
+
+        let (key, kdf) = test_key();
+        let sealed = seal_store(&key, &kdf, &store).unwrap();
+        assert!(secrets_vault::is_vault(&sealed));
+        assert!(!sealed.contains("sk-test"));
+
+        let opened = open_store(&key, &sealed).unwrap();
+        assert_eq!(opened.secrets, store.secrets);
+    }
+
+    #[test]
+    fn set_value_keeps_history() {
+        let mut store = SecretStore::default();
+        let now = Utc::now();
+        store.set_value("KEY", "a".into(), now);
+        store.set_value("KEY", "a".into(), now);
+        assert!(!store.history.contains_key("KEY"));
+        for value in ["b", "c", "d", "e", "f", "g"] {
+            store.set_value("KEY", value.into(), now);
+        }
+        let history: Vec<&str> = store.history["KEY"]
+            .iter()
+            .map(|r| r.value.as_str())
+            .collect();
+        assert_eq!(history, vec!["f", "e", "d", "c", "b"]);
+        assert_eq!(store.meta["KEY"].created_at, Some(now));
+    }
+
+    #[test]
+    fn zeroize_clears_values_and_history() {
+        let mut store = SecretStore::default();
+        let now = Utc::now();
+        store.set_value("KEY", "old".into(), now);
+        store.set_value("KEY", "new".into(), now);
+        store.zeroize_values();
+        assert_eq!(store.secrets["KEY"], "");
+        assert_eq!(store.history["KEY"][0].value, "");
+    }
+}
@@ -0,0 +1,455 @@
+// src-tauri/src/secrets_transfer.rs
+
+use crate::atomic_file;
+use crate::secrets_audit::{self, AuditAction, AuditEntry};
+use crate::secrets_manager::{self, Error, SecretMeta, SecretStore};
+use crate::secrets_vault::{self, KdfParams, VaultEnvelope};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+// 与前端 VALID_KEY_REGEX 一致
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+// 导入时与已有密钥同名的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    Skip,
+    Overwrite,
+    // 以 KEY_2、KEY_3 ... 的名字导入
+    Rename,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: Vec<String>,
+    pub overwritten: Vec<String>,
+    // (原名, 新名)
+    pub renamed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+    // 名称不合法而被忽略的键
+    pub invalid: Vec<String>,
+    // 值与本地相同、只更新了描述等元数据的键
+    pub metadata_updated: Vec<String>,
+    // 导入包中的使用限制与本地不同，保留了本地限制的键
+    pub restrictions_kept: Vec<String>,
+    // 使用限制被导入包中的限制替换的键 (仅在选择导入使用限制时)
+    pub restrictions_replaced: Vec<String>,
+}
+
+// 合并元数据时对使用限制的处理结果
+#[derive(Debug, PartialEq)]
+enum Restrictions {
+    Unchanged,
+    Kept,
+    Replaced,
+}
+
+// 解析 .env 中引号内的值
+fn parse_quoted(rest: &str, quote: char) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => return Some(value),
+            // 单引号内不处理转义
+            '\\' if quote == '"' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// 解析 .env 文件：支持 `export KEY=VALUE`、# 注释、单双引号和行尾注释
+pub fn parse_dotenv(text: &str) -> Result<Vec<(String, String)>, String> {
+    // Windows 编辑器保存的文件可能带有 BOM
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Line {}: expected KEY=VALUE", index + 1))?;
+        let rest = rest.trim();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => parse_quoted(&rest[1..], quote)
+                .ok_or_else(|| format!("Line {}: unterminated quoted value", index + 1))?,
+            // 未加引号时，空白后的 # 之后为注释
+            _ => match rest.find(" #") {
+                Some(pos) => rest[..pos].trim_end().to_string(),
+                None => rest.to_string(),
+            },
+        };
+        entries.push((key.trim().to_string(), value));
+    }
+    Ok(entries)
+}
+
+// 读取待导入的文件：加密包 (需要口令)、JSON 或 .env
+fn read_import_file(path: &Path, passphrase: Option<&str>) -> Result<SecretStore, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if secrets_vault::is_vault(&text) {
+        let passphrase = passphrase
+            .filter(|p| !p.is_empty())
+            .ok_or("This file is encrypted, a passphrase is required")?;
+        let envelope = VaultEnvelope::parse(&text).map_err(|e| e.to_string())?;
+        let key = secrets_vault::derive_key(passphrase, envelope.kdf()).map_err(|e| e.to_string())?;
+        let plaintext = secrets_vault::open(&key, &envelope).map_err(|e| match e {
+            Error::WrongPassword => "Wrong passphrase".to_string(),
+            e => e.to_string(),
+        })?;
+        return SecretStore::parse(&plaintext).map_err(|e| e.to_string());
+    }
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        || text.trim_start().starts_with('{');
+    if is_json {
+        return SecretStore::parse(text.as_bytes())
+            .map_err(|e| format!("Expected a JSON object of string values: {}", e));
+    }
+
+    let mut store = SecretStore::default();
+    store.secrets.extend(parse_dotenv(&text)?);
+    Ok(store)
+}
+
+// 为重名的键找一个未使用的名字
+fn unused_name(key: &str, secrets: &HashMap<String, String>) -> String {
+    (2..)
+        .map(|n| format!("{}_{}", key, n))
+        .find(|name| !secrets.contains_key(name))
+        .unwrap()
+}
+
+// 把导入的元数据合并到 target；local 为本地同名密钥原有的元数据 (新密钥为 None)
+// 本地已有的使用限制默认保留 (重命名导入的密钥也沿用)，import_restrictions 时才用导入包中的限制替换
+fn merge_meta(
+    target: &mut SecretMeta,
+    source: Option<&SecretMeta>,
+    local: Option<&SecretMeta>,
+    import_restrictions: bool,
+) -> Restrictions {
+    // 时间戳以本机为准
+    if let Some(source) = source {
+        target.description = source.description.clone();
+        target.provider = source.provider.clone();
+        target.expires_at = source.expires_at;
+    }
+
+    let (from, result) = match (local, source) {
+        (_, None) => (local, Restrictions::Unchanged),
+        (None, Some(source)) => (Some(source), Restrictions::Unchanged),
+        (Some(local), Some(source))
+            if local.allowed_hosts == source.allowed_hosts && local.allowed_headers == source.allowed_headers =>
+        {
+            (Some(source), Restrictions::Unchanged)
+        }
+        (Some(_), Some(source)) if import_restrictions => (Some(source), Restrictions::Replaced),
+        (Some(local), Some(_)) => (Some(local), Restrictions::Kept),
+    };
+    if let Some(from) = from {
+        target.allowed_hosts = from.allowed_hosts.clone();
+        target.allowed_headers = from.allowed_headers.clone();
+    }
+    result
+}
+
+fn import(
+    app: &AppHandle,
+    path: &Path,
+    conflict: ConflictStrategy,
+    passphrase: Option<&str>,
+    import_restrictions: bool,
+) -> Result<ImportReport, String> {
+    let mut incoming = read_import_file(path, passphrase)?;
+    // 按名称排序，使重命名的结果稳定
+    let entries: BTreeMap<String, String> = std::mem::take(&mut incoming.secrets).into_iter().collect();
+
+    let report = secrets_manager::update_store(app, |store| {
+        let mut report = ImportReport::default();
+        let now = Utc::now();
+        for (key, value) in entries {
+            if !is_valid_key(&key) {
+                report.invalid.push(key);
+                continue;
+            }
+            let same_value = store.secrets.get(&key) == Some(&value);
+            let local = store
+                .secrets
+                .contains_key(&key)
+                .then(|| store.meta.get(&key).cloned().unwrap_or_default());
+            let target = match store.secrets.get(&key) {
+                None => {
+                    report.imported.push(key.clone());
+                    key.clone()
+                }
+                Some(_) if conflict == ConflictStrategy::Skip => {
+                    report.skipped.push(key);
+                    continue;
+                }
+                // 值相同时不改写值 (也不产生历史记录)，元数据按覆盖处理
+                Some(_) if same_value => key.clone(),
+                Some(_) if conflict == ConflictStrategy::Overwrite => {
+                    report.overwritten.push(key.clone());
+                    key.clone()
+                }
+                Some(_) => {
+                    let name = unused_name(&key, &store.secrets);
+                    report.renamed.push((key.clone(), name.clone()));
+                    name
+                }
+            };
+            if !same_value {
+                store.set_value(&target, value, now);
+            }
+
+            // 加密包中带有的描述、使用限制等一并导入
+            let source = incoming.meta.get(&key);
+            let before = store.meta.get(&target).cloned().unwrap_or_default();
+            let mut changed = false;
+            if source.is_some() || local.is_some() {
+                let meta = store.meta.entry(target.clone()).or_default();
+                match merge_meta(meta, source, local.as_ref(), import_restrictions) {
+                    Restrictions::Kept => report.restrictions_kept.push(target.clone()),
+                    Restrictions::Replaced => report.restrictions_replaced.push(target.clone()),
+                    Restrictions::Unchanged => {}
+                }
+                changed = *meta != before;
+            }
+            if same_value {
+                if !changed {
+                    report.skipped.push(key);
+                } else {
+                    report.metadata_updated.push(key);
+                }
+            }
+        }
+        Ok(report)
+    })
+    .map_err(|e| e.to_string())?;
+
+    let written = report
+        .imported
+        .iter()
+        .chain(&report.overwritten)
+        .chain(report.renamed.iter().map(|(_, name)| name));
+    secrets_audit::record(
+        app,
+        written.map(|key| AuditEntry::new(AuditAction::Import, Some(key))).collect(),
+    );
+    Ok(report)
+}
+
+fn export(app: &AppHandle, path: &Path, keys: &[String], passphrase: &str) -> Result<usize, String> {
+    let store = secrets_manager::read_store(app).map_err(|e| e.to_string())?;
+    let mut bundle = SecretStore::default();
+    for key in keys {
+        let value = store
+            .secrets
+            .get(key)
+            .ok_or_else(|| Error::NotFound(key.clone()).to_string())?;
+        bundle.secrets.insert(key.clone(), value.clone());
+        if let Some(meta) = store.meta.get(key) {
+            bundle.meta.insert(key.clone(), meta.clone());
+        }
+    }
+
+    // 每个导出包使用独立的盐，与主密码无关
+    let kdf = KdfParams::generate();
+    let key = secrets_vault::derive_key(passphrase, &kdf).map_err(|e| e.to_string())?;
+    let plaintext = zeroize::Zeroizing::new(serde_json::to_vec(&bundle).map_err(|e| e.to_string())?);
+    let envelope = secrets_vault::seal(&key, &kdf, &plaintext).map_err(|e| e.to_string())?;
+    let contents = serde_json::to_string_pretty(&envelope).map_err(|e| e.to_string())?;
+    atomic_file::write_atomic(path, contents.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(bundle.secrets.len())
+}
+
+// --- Tauri Commands ---
+
+/// 从 .env、JSON 或加密导出包导入密钥
+/// 同名密钥默认保留本地的使用限制，import_restrictions 为 true 时使用导入包中的限制
+#[tauri::command]
+pub async fn import_secrets(
+    app: AppHandle,
+    path: String,
+    conflict: ConflictStrategy,
+    passphrase: Option<String>,
+    import_restrictions: Option<bool>,
+) -> Result<ImportReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        import(
+            &app,
+            Path::new(&path),
+            conflict,
+            passphrase.as_deref(),
+            import_restrictions.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 将选中的密钥导出为用口令加密的文件，返回导出的数量
+#[tauri::command]
+pub async fn export_secrets(
+    app: AppHandle,
+    path: String,
+    keys: Vec<String>,
+    passphrase: String,
+) -> Result<usize, String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+    if keys.is_empty() {
+        return Err("No secrets selected".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || export(&app, Path::new(&path), &keys, &passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted(hosts: &[&str]) -> SecretMeta {
+        SecretMeta {
+            allowed_hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            description: "from bundle".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dotenv_quotes_and_escapes() {
+        let text = "A=\"line\\nnext\\t\\\"q\\\"\"\nB='raw\\n # kept'\nC=\"\"\n";
+        assert_eq!(
+            parse_dotenv(text).unwrap(),
+            pairs(&[
+                ("A", "line\nnext\t\"q\""),
+                ("B", "raw\\n # kept"),
+                ("C", "")
+            ])
+        );
+    }
+
+    #[test]
+    fn dotenv_export_and_comments() {
+        let text = "# comment\n\nexport  A = 1 # trailing\nB=x#y\n  C=\"v\" # after quote\n";
+        assert_eq!(
+            parse_dotenv(text).unwrap(),
+            pairs(&[("A", "1"), ("B", "x#y"), ("C", "v")])
+        );
+    }
+
+    #[test]
+    fn dotenv_strips_bom() {
+        let text = "\u{feff}FIRST=1\nSECOND=2";
+        assert_eq!(
+            parse_dotenv(text).unwrap(),
+            pairs(&[("FIRST", "1"), ("SECOND", "2")])
+        );
+    }
+
+    #[test]
+    fn dotenv_errors_report_line() {
+        assert_eq!(
+            parse_dotenv("A=1\nB=\"open").unwrap_err(),
+            "Line 2: unterminated quoted value"
+        );
+        assert_eq!(
+            parse_dotenv("JUSTKEY").unwrap_err(),
+            "Line 1: expected KEY=VALUE"
+        );
+    }
+
+    #[test]
+    fn new_key_takes_bundle_restrictions() {
+        let source = restricted(&["api.example.com"]);
+        let mut target = SecretMeta::default();
+        assert_eq!(
+            merge_meta(&mut target, Some(&source), None, false),
+            Restrictions::Unchanged
+        );
+        assert_eq!(target, source);
+    }
+
+    #[test]
+    fn local_restrictions_are_kept_by_default() {
+        let local = restricted(&["api.example.com"]);
+        let source = restricted(&["*"]);
+        let mut target = local.clone();
+        assert_eq!(
+            merge_meta(&mut target, Some(&source), Some(&local), false),
+            Restrictions::Kept
+        );
+        assert_eq!(target.allowed_hosts, vec!["api.example.com"]);
+        assert_eq!(target.description, "from bundle");
+
+        // 重命名导入的新密钥同样沿用本地限制
+        let mut renamed = SecretMeta::default();
+        assert_eq!(
+            merge_meta(&mut renamed, Some(&source), Some(&local), false),
+            Restrictions::Kept
+        );
+        assert_eq!(renamed.allowed_hosts, vec!["api.example.com"]);
+    }
+
+    #[test]
+    fn restrictions_replaced_when_opted_in() {
+        let local = restricted(&["api.example.com"]);
+        let source = restricted(&["*"]);
+        let mut target = local.clone();
+        assert_eq!(
+            merge_meta(&mut target, Some(&source), Some(&local), true),
+            Restrictions::Replaced
+        );
+        assert_eq!(target.allowed_hosts, vec!["*"]);
+
+        // 限制相同时不算替换
+        let mut target = local.clone();
+        assert_eq!(
+            merge_meta(&mut target, Some(&local), Some(&local), true),
+            Restrictions::Unchanged
+        );
+    }
+
+    #[test]
+    fn missing_bundle_meta_keeps_local() {
+        let local = restricted(&["api.example.com"]);
+        let mut renamed = SecretMeta::default();
+        assert_eq!(
+            merge_meta(&mut renamed, None, Some(&local), true),
+            Restrictions::Unchanged
+        );
+        assert_eq!(renamed.allowed_hosts, vec!["api.example.com"]);
+        assert_eq!(renamed.description, "");
+    }
+}
@@ -0,0 +1,410 @@
+// src-tauri/src/secrets_validation.rs
+
+use crate::proxy_server::{self, ProxyClient};
+use crate::secrets_audit::{self, AuditAction, AuditEntry};
+use crate::secrets_manager;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+// 返回给前端的错误信息最大长度
+const MAX_MESSAGE_LEN: usize = 300;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const AZURE_API_VERSION: &str = "2024-10-21";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    OpenaiCompatible,
+    Anthropic,
+    Google,
+    Deepseek,
+    Xai,
+    Azure,
+}
+
+impl ProviderKind {
+    // 默认的 API 地址；Azure 的地址因资源而异，必须由调用方提供
+    fn default_base_url(self) -> Option<&'static str> {
+        match self {
+            ProviderKind::OpenaiCompatible => Some("https://api.openai.com/v1"),
+            ProviderKind::Anthropic => Some("https://api.anthropic.com/v1"),
+            ProviderKind::Google => Some("https://generativelanguage.googleapis.com/v1beta"),
+            ProviderKind::Deepseek => Some("https://api.deepseek.com"),
+            ProviderKind::Xai => Some("https://api.x.ai/v1"),
+            ProviderKind::Azure => None,
+        }
+    }
+
+    // 选一个不消耗额度的接口：列出模型
+    fn build_request(self, client: &reqwest::Client, base_url: &str, key: &str) -> RequestBuilder {
+        match self {
+            ProviderKind::Anthropic => client
+                .get(format!("{}/models", base_url))
+                .header("x-api-key", key)
+                .header("anthropic-version", ANTHROPIC_VERSION),
+            ProviderKind::Google => client
+                .get(format!("{}/models", base_url))
+                .header("x-goog-api-key", key),
+            ProviderKind::Azure => client
+                .get(format!("{}/openai/models", base_url))
+                .query(&[("api-version", AZURE_API_VERSION)])
+                .header("api-key", key),
+            ProviderKind::OpenaiCompatible | ProviderKind::Deepseek | ProviderKind::Xai => client
+                .get(format!("{}/models", base_url))
+                .bearer_auth(key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretTestStatus {
+    Valid,
+    // 密钥错误、过期或被吊销
+    Invalid,
+    // 额度用尽或被限流
+    Quota,
+    // 无法连接到服务商
+    NetworkError,
+    // 服务商返回了其他错误 (例如 5xx)
+    ProviderError,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SecretTestResult {
+    pub status: SecretTestStatus,
+    pub http_status: Option<u16>,
+    pub message: Option<String>,
+    pub latency_ms: u64,
+}
+
+// 根据状态码和响应内容判断结果
+fn classify(http_status: u16, body: &str) -> SecretTestStatus {
+    let lower = body.to_lowercase();
+    match http_status {
+        200..=299 => SecretTestStatus::Valid,
+        401 | 403 => SecretTestStatus::Invalid,
+        402 | 429 => SecretTestStatus::Quota,
+        // Google 对无效的密钥返回 400
+        400 if lower.contains("api_key_invalid") || lower.contains("api key not valid") => {
+            SecretTestStatus::Invalid
+        }
+        400 if lower.contains("quota") || lower.contains("billing") || lower.contains("credit") => {
+            SecretTestStatus::Quota
+        }
+        _ => SecretTestStatus::ProviderError,
+    }
+}
+
+// 取出错误信息，并确保其中不包含密钥本身
+fn error_message(body: &str, key: &str) -> Option<String> {
+    let value: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    let message = value
+        .as_ref()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .or_else(|| v.get("message"))
+                .or_else(|| v.get("error"))
+        })
+        .and_then(|m| m.as_str())
+        .unwrap_or(body)
+        .trim();
+    if message.is_empty() {
+        return None;
+    }
+    let message = if key.is_empty() { message.to_string() } else { message.replace(key, "***") };
+    Some(message.chars().take(MAX_MESSAGE_LEN).collect())
+}
+
+// 发送验证请求并判断结果；client 不应跟随重定向，以免密钥被转发到使用限制之外的主机
+async fn send_test(client: &reqwest::Client, provider: ProviderKind, base_url: &str, key: &str) -> SecretTestResult {
+    let started = Instant::now();
+    let response = provider
+        .build_request(client, base_url, key)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            return SecretTestResult {
+                status: SecretTestStatus::NetworkError,
+                http_status: None,
+                message: error_message(&e.to_string(), key),
+                latency_ms,
+            };
+        }
+    };
+    let http_status = response.status().as_u16();
+    let body = response.text().await.unwrap_or_default();
+    let status = classify(http_status, &body);
+    SecretTestResult {
+        status,
+        http_status: Some(http_status),
+        message: if status == SecretTestStatus::Valid { None } else { error_message(&body, key) },
+        latency_ms,
+    }
+}
+
+// --- Tauri Commands ---
+
+/// 用一次不消耗额度的请求验证密钥是否可用
+/// base_url 可覆盖默认地址 (Azure 必填，也可指向本地的模拟服务)
+#[tauri::command]
+pub async fn test_secret(
+    app: AppHandle,
+    key: String,
+    provider: ProviderKind,
+    base_url: Option<String>,
+) -> Result<SecretTestResult, String> {
+    let store = secrets_manager::read_store(&app).map_err(|e| e.to_string())?;
+    let value = store
+        .secrets
+        .get(&key)
+        .cloned()
+        .ok_or_else(|| secrets_manager::Error::NotFound(key.clone()).to_string())?;
+
+    let base_url = base_url
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .or_else(|| provider.default_base_url().map(str::to_string))
+        .ok_or("A base URL is required for this provider")?;
+    let base_url = base_url.trim_end_matches('/');
+
+    // 与代理相同的使用限制，防止借助自定义地址把密钥发送到其他服务器
+    let target = url::Url::parse(base_url).map_err(|_| format!("Invalid base URL: {}", base_url))?;
+    let host = target.host_str().unwrap_or_default().to_lowercase();
+    let audit = |status: Option<u16>, action: AuditAction| {
+        secrets_audit::record(
+            &app,
+            vec![AuditEntry {
+                host: Some(host.clone()),
+                method: Some("GET".to_string()),
+                status,
+                origin: Some("test_secret".to_string()),
+                ..AuditEntry::new(action, Some(&key))
+            }],
+        );
+    };
+    let header = match provider {
+        ProviderKind::Anthropic => "x-api-key",
+        ProviderKind::Google => "x-goog-api-key",
+        ProviderKind::Azure => "api-key",
+        _ => "authorization",
+    };
+    let port = target.port_or_known_default();
+    if let Err((code, message)) =
+        proxy_server::check_secret_access(&key, store.meta.get(&key), &host, port, Some(header))
+    {
+        audit(Some(code.as_u16()), AuditAction::Denied);
+        return Err(message);
+    }
+
+    let client = app.state::<ProxyClient>().no_redirect.clone();
+    let result = send_test(&client, provider, base_url, &value).await;
+    audit(result.http_status, AuditAction::Test);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{header, HeaderMap, StatusCode},
+        response::{IntoResponse, Redirect},
+        routing::get,
+        Router,
+    };
+
+    const KEY: &str = "sk-test-1234567890";
+
+    fn no_redirect_client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+    }
+
+    fn request(provider: ProviderKind, base_url: &str) -> reqwest::Request {
+        provider
+            .build_request(&reqwest::Client::new(), base_url, KEY)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn classify_by_status_and_body() {
+        assert_eq!(classify(200, ""), SecretTestStatus::Valid);
+        assert_eq!(classify(401, ""), SecretTestStatus::Invalid);
+        assert_eq!(classify(403, ""), SecretTestStatus::Invalid);
+        assert_eq!(classify(402, ""), SecretTestStatus::Quota);
+        assert_eq!(classify(429, ""), SecretTestStatus::Quota);
+        assert_eq!(
+            classify(
+                400,
+                r#"{"error":{"status":"INVALID_ARGUMENT","details":[{"reason":"API_KEY_INVALID"}]}}"#
+            ),
+            SecretTestStatus::Invalid
+        );
+        assert_eq!(
+            classify(400, "API key not valid. Please pass a valid API key."),
+            SecretTestStatus::Invalid
+        );
+        assert_eq!(
+            classify(400, "Your credit balance is too low"),
+            SecretTestStatus::Quota
+        );
+        assert_eq!(
+            classify(400, "bad request"),
+            SecretTestStatus::ProviderError
+        );
+        assert_eq!(classify(500, "quota"), SecretTestStatus::ProviderError);
+    }
+
+    #[test]
+    fn error_message_redacts_key() {
+        let body = format!(
+            r#"{{"error":{{"message":"Incorrect API key provided: {}"}}}}"#,
+            KEY
+        );
+        assert_eq!(
+            error_message(&body, KEY).unwrap(),
+            "Incorrect API key provided: ***"
+        );
+
+        // 其他格式的错误信息和纯文本同样要去掉密钥
+        assert_eq!(
+            error_message(&format!(r#"{{"message":"bad {}"}}"#, KEY), KEY).unwrap(),
+            "bad ***"
+        );
+        assert_eq!(
+            error_message(&format!(r#"{{"error":"bad {}"}}"#, KEY), KEY).unwrap(),
+            "bad ***"
+        );
+        assert_eq!(
+            error_message(&format!("  key {} rejected ", KEY), KEY).unwrap(),
+            "key *** rejected"
+        );
+        assert_eq!(error_message("   ", KEY), None);
+
+        let long = "x".repeat(MAX_MESSAGE_LEN * 2);
+        assert_eq!(
+            error_message(&long, KEY).unwrap().chars().count(),
+            MAX_MESSAGE_LEN
+        );
+    }
+
+    #[test]
+    fn request_shapes_per_provider() {
+        let req = request(ProviderKind::OpenaiCompatible, "https://api.openai.com/v1");
+        assert_eq!(req.method(), reqwest::Method::GET);
+        assert_eq!(req.url().as_str(), "https://api.openai.com/v1/models");
+        assert_eq!(
+            req.headers()["authorization"],
+            format!("Bearer {}", KEY).as_str()
+        );
+
+        for provider in [ProviderKind::Deepseek, ProviderKind::Xai] {
+            let base_url = provider.default_base_url().unwrap();
+            let req = request(provider, base_url);
+            assert_eq!(req.url().as_str(), format!("{}/models", base_url));
+            assert_eq!(
+                req.headers()["authorization"],
+                format!("Bearer {}", KEY).as_str()
+            );
+        }
+
+        let req = request(ProviderKind::Anthropic, "https://api.anthropic.com/v1");
+        assert_eq!(req.url().as_str(), "https://api.anthropic.com/v1/models");
+        assert_eq!(req.headers()["x-api-key"], KEY);
+        assert_eq!(req.headers()["anthropic-version"], ANTHROPIC_VERSION);
+        assert!(!req.headers().contains_key("authorization"));
+
+        let req = request(
+            ProviderKind::Google,
+            "https://generativelanguage.googleapis.com/v1beta",
+        );
+        assert_eq!(
+            req.url().as_str(),
+            "https://generativelanguage.googleapis.com/v1beta/models"
+        );
+        assert_eq!(req.headers()["x-goog-api-key"], KEY);
+        // 密钥不能出现在 URL 中
+        assert!(!req.url().as_str().contains(KEY));
+
+        let req = request(ProviderKind::Azure, "https://res.openai.azure.com");
+        assert_eq!(
+            req.url().as_str(),
+            format!(
+                "https://res.openai.azure.com/openai/models?api-version={}",
+                AZURE_API_VERSION
+            )
+        );
+        assert_eq!(req.headers()["api-key"], KEY);
+        assert_eq!(ProviderKind::Azure.default_base_url(), None);
+    }
+
+    async fn models(headers: HeaderMap) -> impl IntoResponse {
+        match headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(auth) if auth == format!("Bearer {}", KEY) => {
+                (StatusCode::OK, r#"{"data":[]}"#.to_string())
+            }
+            auth => (
+                StatusCode::UNAUTHORIZED,
+                format!(
+                    r#"{{"error":{{"message":"Incorrect API key provided: {}"}}}}"#,
+                    auth.unwrap_or_default()
+                ),
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_test_against_mock_provider() {
+        let router = Router::new().route("/v1/models", get(models)).route(
+            "/moved/models",
+            get(|| async { Redirect::temporary("/v1/models") }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let client = no_redirect_client();
+        let provider = ProviderKind::OpenaiCompatible;
+
+        let result = send_test(&client, provider, &format!("{}/v1", base), KEY).await;
+        assert_eq!(result.status, SecretTestStatus::Valid);
+        assert_eq!(result.http_status, Some(200));
+        assert_eq!(result.message, None);
+
+        let wrong = "sk-wrong-key";
+        let result = send_test(&client, provider, &format!("{}/v1", base), wrong).await;
+        assert_eq!(result.status, SecretTestStatus::Invalid);
+        assert_eq!(result.http_status, Some(401));
+        assert_eq!(
+            result.message.as_deref(),
+            Some("Incorrect API key provided: Bearer ***")
+        );
+
+        // 重定向不会被跟随，密钥不会再次发出
+        let result = send_test(&client, provider, &format!("{}/moved", base), KEY).await;
+        assert_eq!(result.status, SecretTestStatus::ProviderError);
+        assert_eq!(result.http_status, Some(307));
+
+        // 连接不上
+        drop(client);
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", closed.local_addr().unwrap());
+        drop(closed);
+        let result = send_test(&no_redirect_client(), provider, &url, KEY).await;
+        assert_eq!(result.status, SecretTestStatus::NetworkError);
+        assert_eq!(result.http_status, None);
+    }
+}
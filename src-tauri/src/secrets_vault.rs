@@ -0,0 +1,247 @@
+// src-tauri/src/secrets_vault.rs
+
+use crate::secrets_manager::Error;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+// 文件格式标识与版本，格式变化时递增 VAULT_VERSION
+pub const VAULT_FORMAT: &str = "pulsar-vault";
+pub const VAULT_VERSION: u32 = 1;
+const CIPHER: &str = "xchacha20poly1305";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+// Argon2id 参数，随文件保存，以后调整默认值不影响已有的文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: String, // hex
+}
+
+impl KdfParams {
+    // 每次设置新密码时生成新的盐
+    pub fn generate() -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            algorithm: "argon2id".to_string(),
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+            salt: hex::encode(salt),
+        }
+    }
+}
+
+// 文件头：除密文外的所有字段，整体作为 AEAD 的附加数据，防止被篡改
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultHeader {
+    format: String,
+    version: u32,
+    cipher: String,
+    kdf: KdfParams,
+    nonce: String, // hex
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultEnvelope {
+    #[serde(flatten)]
+    header: VaultHeader,
+    ciphertext: String, // hex
+}
+
+pub type VaultKey = Zeroizing<[u8; KEY_LEN]>;
+
+/// 由密码派生加密密钥 (较慢，应在后台线程调用)
+pub fn derive_key(password: &str, kdf: &KdfParams) -> Result<VaultKey, Error> {
+    if kdf.algorithm != "argon2id" {
+        return Err(Error::InvalidVault(format!("Unsupported KDF: {}", kdf.algorithm)));
+    }
+    let salt = hex::decode(&kdf.salt).map_err(|e| Error::InvalidVault(e.to_string()))?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(KEY_LEN))
+        .map_err(|e| Error::InvalidVault(e.to_string()))?;
+
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), &salt, &mut key[..])
+        .map_err(|e| Error::Crypto(e.to_string()))?;
+    Ok(key)
+}
+
+/// 判断文件内容是否为加密格式 (旧版 secrets.json 是普通的 JSON 对象)
+pub fn is_vault(contents: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(contents)
+        .ok()
+        .and_then(|v| v.get("format").and_then(|f| f.as_str()).map(|f| f == VAULT_FORMAT))
+        .unwrap_or(false)
+}
+
+/// 用已派生的密钥加密，每次使用新的随机 nonce
+pub fn seal(key: &VaultKey, kdf: &KdfParams, plaintext: &[u8]) -> Result<VaultEnvelope, Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let header = VaultHeader {
+        format: VAULT_FORMAT.to_string(),
+        version: VAULT_VERSION,
+        cipher: CIPHER.to_string(),
+        kdf: kdf.clone(),
+        nonce: hex::encode(nonce),
+    };
+    let aad = serde_json::to_vec(&header)?;
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&key[..]).map_err(|e| Error::Crypto(e.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: &aad })
+        .map_err(|e| Error::Crypto(e.to_string()))?;
+
+    Ok(VaultEnvelope {
+        header,
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+/// 解密；密码错误或文件被篡改时返回 WrongPassword
+pub fn open(key: &VaultKey, envelope: &VaultEnvelope) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let header = &envelope.header;
+    let nonce = hex::decode(&header.nonce).map_err(|e| Error::InvalidVault(e.to_string()))?;
+    if nonce.len() != NONCE_LEN {
+        return Err(Error::InvalidVault("Invalid nonce length".to_string()));
+    }
+    let ciphertext = hex::decode(&envelope.ciphertext).map_err(|e| Error::InvalidVault(e.to_string()))?;
+    let aad = serde_json::to_vec(header)?;
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&key[..]).map_err(|e| Error::Crypto(e.to_string()))?;
+    cipher
+        .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+        .map(Zeroizing::new)
+        .map_err(|_| Error::WrongPassword)
+}
+
+impl VaultEnvelope {
+    /// 解析并检查文件头
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        let envelope: VaultEnvelope = serde_json::from_str(contents)?;
+        let header = &envelope.header;
+        if header.format != VAULT_FORMAT {
+            return Err(Error::InvalidVault(format!("Unknown format: {}", header.format)));
+        }
+        if header.version > VAULT_VERSION {
+            return Err(Error::InvalidVault(format!(
+                "Vault version {} is newer than supported version {}",
+                header.version, VAULT_VERSION
+            )));
+        }
+        if header.cipher != CIPHER {
+            return Err(Error::InvalidVault(format!("Unsupported cipher: {}", header.cipher)));
+        }
+        Ok(envelope)
+    }
+
+    pub fn kdf(&self) -> &KdfParams {
+        &self.header.kdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试用的低成本参数，避免每次派生都占用 64 MiB
+    fn test_kdf() -> KdfParams {
+        KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            ..KdfParams::generate()
+        }
+    }
+
+    fn sealed(password: &str, plaintext: &[u8]) -> (KdfParams, String) {
+        let kdf = test_kdf();
+        let key = derive_key(password, &kdf).unwrap();
+        let envelope = seal(&key, &kdf, plaintext).unwrap();
+        (kdf, serde_json::to_string(&envelope).unwrap())
+    }
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let (kdf, contents) = sealed("correct horse", b"{\"OPENAI_API_KEY\":\"sk-test\"}");
+        assert!(is_vault(&contents));
+        assert!(!contents.contains("sk-test"));
+
+        let envelope = VaultEnvelope::parse(&contents).unwrap();
+        assert_eq!(envelope.kdf(), &kdf);
+        let key = derive_key("correct horse", envelope.kdf()).unwrap();
+        assert_eq!(
+            &open(&key, &envelope).unwrap()[..],
+            b"{\"OPENAI_API_KEY\":\"sk-test\"}"
+        );
+    }
+
+    #[test]
+    fn each_seal_uses_a_new_nonce() {
+        let kdf = test_kdf();
+        let key = derive_key("pw", &kdf).unwrap();
+        let a = seal(&key, &kdf, b"same").unwrap();
+        let b = seal(&key, &kdf, b"same").unwrap();
+        assert_ne!(a.header.nonce, b.header.nonce);
+        assert_ne!(a.ciphertext, b.ciphertext);
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        let (_, contents) = sealed("correct horse", b"{}");
+        let envelope = VaultEnvelope::parse(&contents).unwrap();
+        let key = derive_key("battery staple", envelope.kdf()).unwrap();
+        assert!(matches!(open(&key, &envelope), Err(Error::WrongPassword)));
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        let (kdf, contents) = sealed("pw", b"{}");
+        let key = derive_key("pw", &kdf).unwrap();
+
+        // 降低 KDF 强度等对文件头的修改会导致附加数据校验失败
+        let mut value: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        value["kdf"]["iterations"] = 2.into();
+        let envelope = VaultEnvelope::parse(&value.to_string()).unwrap();
+        assert!(matches!(open(&key, &envelope), Err(Error::WrongPassword)));
+
+        let mut value: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        let ciphertext = value["ciphertext"].as_str().unwrap().to_string();
+        let flipped = if ciphertext.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        value["ciphertext"] = format!("{}{}", flipped, &ciphertext[1..]).into();
+        let envelope = VaultEnvelope::parse(&value.to_string()).unwrap();
+        assert!(matches!(open(&key, &envelope), Err(Error::WrongPassword)));
+    }
+
+    #[test]
+    fn parse_checks_the_header() {
+        let (_, contents) = sealed("pw", b"{}");
+        let mut value: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        value["version"] = (VAULT_VERSION + 1).into();
+        assert!(matches!(
+            VaultEnvelope::parse(&value.to_string()),
+            Err(Error::InvalidVault(_))
+        ));
+
+        let mut value: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        value["cipher"] = "aes-256-gcm".into();
+        assert!(VaultEnvelope::parse(&value.to_string()).is_err());
+
+        assert!(!is_vault("{\"OPENAI_API_KEY\":\"sk-test\"}"));
+        assert!(!is_vault("not json"));
+    }
+}
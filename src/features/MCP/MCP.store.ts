@@ -5,10 +5,20 @@ import { useFileContent } from "@/features/FileSystem/composables/useFileContent
 import { useFileSystemStore } from "@/features/FileSystem/FileSystem.store";
 
 // 定义类型以增强代码健壮性
+// command 与 script (已注册脚本的名称) 二选一
 export interface McpServerConfig {
-  command: string;
+  command?: string;
   args?: string[];
   env?: Record<string, string>;
+  script?: string;
+  limits?: {
+    cpuTimeSecs?: number;
+    memoryMb?: number;
+    maxOpenFiles?: number;
+    nice?: number;
+    useCgroup?: boolean;
+  };
+  restricted?: boolean;
   [key: string]: any;
 }
 
@@ -5,13 +5,130 @@ import { push } from "notivue";
 import { defineStore } from "pinia";
 import { ref } from "vue";
 
+// plaintext: 旧版未加密的密钥文件，首次解锁时会用主密码加密
+export type SecretsStatus = "plaintext" | "locked" | "unlocked";
+
+// 密钥的使用限制，列表为空表示不限制
+export interface SecretRestrictions {
+  allowed_hosts: string[];
+  allowed_headers: string[];
+}
+
+export interface SecretProfiles {
+  active: string;
+  profiles: string[];
+}
+
+export type AuditAction =
+  | "substitute"
+  | "denied"
+  | "script_grant"
+  | "script_grant_restricted"
+  | "write"
+  | "delete"
+  | "rollback"
+  | "import"
+  | "test"
+  | "clear";
+
+// 审计日志只包含密钥名称，不包含值；timestamp 为毫秒时间戳
+export interface AuditEntry {
+  timestamp: number;
+  action: AuditAction;
+  key: string | null;
+  profile: string | null;
+  host: string | null;
+  method: string | null;
+  status: number | null;
+  origin: string | null;
+}
+
+export interface AuditQuery {
+  key?: string;
+  action?: AuditAction;
+  since?: number;
+  until?: number;
+  limit?: number;
+}
+
+export type ProviderKind =
+  | "openai_compatible"
+  | "anthropic"
+  | "google"
+  | "deepseek"
+  | "xai"
+  | "azure";
+
+export interface SecretTestResult {
+  status: "valid" | "invalid" | "quota" | "network_error" | "provider_error";
+  http_status: number | null;
+  message: string | null;
+  latency_ms: number;
+}
+
+export type ImportConflict = "skip" | "overwrite" | "rename";
+
+export interface ImportReport {
+  imported: string[];
+  overwritten: string[];
+  renamed: [string, string][];
+  skipped: string[];
+  invalid: string[];
+  metadata_updated: string[];
+  // 导入包中的使用限制与本地不同时，保留本地限制 / 被替换的键
+  restrictions_kept: string[];
+  restrictions_replaced: string[];
+}
+
+// 密钥的元数据 (不包含值)，时间均为毫秒时间戳
+export interface SecretInfo extends SecretRestrictions {
+  key: string;
+  description: string;
+  provider: string | null;
+  created_at: number | null;
+  updated_at: number | null;
+  expires_at: number | null;
+  expired: boolean;
+  // 可回滚的历史版本的替换时间，最新的在前
+  history: number[];
+}
+
 export const useSecretsStore = defineStore("secrets", () => {
   const keyList = ref<string[]>([]);
+  const status = ref<SecretsStatus>("plaintext");
+  const profiles = ref<SecretProfiles>({ active: "default", profiles: ["default"] });
 
   // 预编译正则，匹配 Rust 后端的逻辑：仅允许字母、数字、下划线、点、减号
   const VALID_KEY_REGEX = /^[a-zA-Z0-9_.-]+$/;
 
+  async function loadStatus() {
+    status.value = await invoke<SecretsStatus>("get_secrets_status");
+    return status.value;
+  }
+
+  // 解锁密钥库 (或将明文文件迁移为加密格式)
+  async function unlock(password: string) {
+    await invoke("unlock_secrets", { password });
+    await loadStatus();
+    await loadKeys();
+  }
+
+  async function lock() {
+    await invoke("lock_secrets");
+    keyList.value = [];
+    await loadStatus();
+  }
+
+  async function changeMasterPassword(oldPassword: string, newPassword: string) {
+    await invoke("change_master_password", { oldPassword, newPassword });
+    await loadStatus();
+  }
+
   async function loadKeys() {
+    if ((await loadStatus()) === "locked") {
+      keyList.value = [];
+      return;
+    }
     try {
       const keys = await invoke<string[]>("get_all_available_keys");
       keyList.value = keys || [];
@@ -52,6 +169,106 @@ export const useSecretsStore = defineStore("secrets", () => {
     }
   }
 
+  async function getRestrictions(key: string) {
+    return await invoke<SecretRestrictions>("get_secret_restrictions", { key });
+  }
+
+  // 例如 allowedHosts: ["api.openai.com"]，allowedHeaders: ["authorization"]
+  async function setRestrictions(key: string, allowedHosts: string[], allowedHeaders: string[]) {
+    await invoke("set_secret_restrictions", { key, allowedHosts, allowedHeaders });
+  }
+
+  async function listMetadata() {
+    return await invoke<SecretInfo[]>("list_secret_metadata");
+  }
+
+  async function updateMetadata(
+    key: string,
+    description: string,
+    provider: string | null,
+    expiresAt: number | null,
+  ) {
+    await invoke("update_secret_metadata", { key, description, provider, expiresAt });
+  }
+
+  // index 为 0 时回滚到上一次的值
+  async function rollbackSecretKey(key: string, index = 0) {
+    await invoke("rollback_secret_key", { key, index });
+  }
+
+  // 支持 .env、JSON 和加密导出包 (需要 passphrase)
+  // importRestrictions 为 true 时，同名密钥的使用限制以导入包为准
+  async function importSecrets(
+    path: string,
+    conflict: ImportConflict,
+    passphrase?: string,
+    importRestrictions = false
+  ) {
+    const report = await invoke<ImportReport>("import_secrets", {
+      path,
+      conflict,
+      passphrase: passphrase || null,
+      importRestrictions,
+    });
+    await loadKeys();
+    return report;
+  }
+
+  async function exportSecrets(path: string, keys: string[], passphrase: string) {
+    return await invoke<number>("export_secrets", { path, keys, passphrase });
+  }
+
+  async function loadProfiles() {
+    profiles.value = await invoke<SecretProfiles>("list_secret_profiles");
+    return profiles.value;
+  }
+
+  async function createProfile(name: string) {
+    await invoke("create_secret_profile", { name });
+    await loadProfiles();
+  }
+
+  async function renameProfile(oldName: string, newName: string) {
+    await invoke("rename_secret_profile", { oldName, newName });
+    await loadProfiles();
+  }
+
+  async function deleteProfile(name: string) {
+    await invoke("delete_secret_profile", { name });
+    await loadProfiles();
+  }
+
+  // 切换后新档案可能处于锁定状态，需要刷新状态和密钥列表
+  async function switchProfile(name: string) {
+    await invoke("switch_secret_profile", { name });
+    await loadProfiles();
+    await loadStatus();
+    await loadKeys();
+  }
+
+  async function queryAudit(query: AuditQuery = {}) {
+    return await invoke<AuditEntry[]>("query_secret_audit", {
+      key: query.key ?? null,
+      action: query.action ?? null,
+      since: query.since ?? null,
+      until: query.until ?? null,
+      limit: query.limit ?? null,
+    });
+  }
+
+  async function clearAudit() {
+    await invoke("clear_secret_audit");
+  }
+
+  // baseUrl 可覆盖服务商的默认地址，Azure 必填
+  async function testSecret(key: string, provider: ProviderKind, baseUrl?: string) {
+    return await invoke<SecretTestResult>("test_secret", {
+      key,
+      provider,
+      baseUrl: baseUrl || null,
+    });
+  }
+
   async function isKeyAvailable(key: string): Promise<boolean> {
     // 这里也可以加一道校验，避免向后端查询明显非法的 key，不过不是必须的
     if (!key || !VALID_KEY_REGEX.test(key)) {
@@ -68,9 +285,30 @@ export const useSecretsStore = defineStore("secrets", () => {
 
   return {
     keyList,
+    status,
+    profiles,
+    loadStatus,
+    unlock,
+    lock,
+    changeMasterPassword,
     loadKeys,
     writeSecretKey,
     deleteSecretKey,
+    getRestrictions,
+    setRestrictions,
+    listMetadata,
+    updateMetadata,
+    rollbackSecretKey,
+    importSecrets,
+    exportSecrets,
+    loadProfiles,
+    createProfile,
+    renameProfile,
+    deleteProfile,
+    switchProfile,
+    queryAudit,
+    clearAudit,
+    testSecret,
     isKeyAvailable,
   };
 });